@@ -0,0 +1,128 @@
+use crate::math;
+
+/// Stopping rule for adaptive sampling.
+///
+/// Pixels are sampled in batches of `min` samples. After each batch, a pixel
+/// stops receiving samples if the standard error of its mean luminance has
+/// fallen below `threshold` times that mean. Checking only between batches
+/// makes it less likely to stop right before a rare, bright sample.
+/// The scene's sample count is the per-pixel budget.
+#[derive(Copy, Clone, Debug)]
+pub struct Adaptive {
+    /// Samples taken between convergence checks
+    min: usize,
+
+    /// Largest acceptable relative standard error
+    threshold: f32,
+}
+
+impl Adaptive {
+    pub fn new(min: usize, threshold: f32) -> Self {
+        Adaptive { min: min.max(2), threshold }
+    }
+
+    /// Whether `estimate` is accurate enough to stop sampling its pixel.
+    /// A pixel never converges before its first batch is complete.
+    pub fn converged(&self, estimate: &Estimate) -> bool {
+        estimate.count() >= self.min
+            && estimate.count().is_multiple_of(self.min)
+            && estimate.error() <= self.threshold * math::max(estimate.mean(), Self::BLACK)
+    }
+
+    /// Luminance below which absolute rather than relative error is used,
+    /// so that noise-free black pixels converge immediately.
+    const BLACK: f32 = 1e-3;
+}
+
+/// Running mean and variance, accumulated with Welford's algorithm.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Estimate {
+    count: usize,
+    mean: f32,
+    m2: f32,
+}
+
+impl Estimate {
+    /// Restore statistics previously read out with `count`, `mean` and `m2`.
+    pub fn new(count: usize, mean: f32, m2: f32) -> Self {
+        Estimate { count, mean, m2 }
+    }
+
+    pub fn add(&mut self, value: f32) {
+        self.count += 1;
+        let delta = value - self.mean;
+        self.mean += delta / self.count as f32;
+        self.m2 += delta * (value - self.mean);
+    }
+
+    /// Combine with statistics gathered from a disjoint set of samples.
+    ///
+    /// See: https://en.wikipedia.org/wiki/Algorithms_for_calculating_variance#Parallel_algorithm
+    pub fn merge(&mut self, rhs: &Estimate) {
+        if rhs.count == 0 { return }
+        let count = self.count + rhs.count;
+        let delta = rhs.mean - self.mean;
+        let (lhs_count, rhs_count) = (self.count as f32, rhs.count as f32);
+        self.mean += delta * rhs_count / count as f32;
+        self.m2 += rhs.m2 + delta * delta * lhs_count * rhs_count / count as f32;
+        self.count = count;
+    }
+
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn mean(&self) -> f32 {
+        self.mean
+    }
+
+    /// Sum of squared differences from the mean.
+    pub fn m2(&self) -> f32 {
+        self.m2
+    }
+
+    /// Unbiased sample variance.
+    pub fn variance(&self) -> f32 {
+        if self.count < 2 { 0.0 } else { self.m2 / (self.count - 1) as f32 }
+    }
+
+    /// Standard error of the mean.
+    pub fn error(&self) -> f32 {
+        if self.count == 0 { 0.0 } else { (self.variance() / self.count as f32).sqrt() }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn estimate(values: &[f32]) -> Estimate {
+        let mut estimate = Estimate::default();
+        values.iter().for_each(|value| estimate.add(*value));
+        estimate
+    }
+
+    fn assert_close(lhs: &Estimate, rhs: &Estimate) {
+        assert_eq!(lhs.count(), rhs.count());
+        assert!((lhs.mean() - rhs.mean()).abs() < 1e-5, "{:?} != {:?}", lhs, rhs);
+        assert!((lhs.m2() - rhs.m2()).abs() < 1e-4, "{:?} != {:?}", lhs, rhs);
+    }
+
+    #[test]
+    fn merge_matches_combined_stream() {
+        let values = [0.5, 2.0, 1.25, 3.5, 0.0, 4.75, 2.5];
+        for split in 0..=values.len() {
+            let (lhs, rhs) = values.split_at(split);
+            let mut merged = estimate(lhs);
+            merged.merge(&estimate(rhs));
+            assert_close(&merged, &estimate(&values));
+        }
+    }
+
+    #[test]
+    fn variance_is_unbiased() {
+        let estimate = estimate(&[1.0, 2.0, 3.0, 4.0]);
+        assert!((estimate.mean() - 2.5).abs() < 1e-5);
+        assert!((estimate.variance() - 5.0 / 3.0).abs() < 1e-5);
+    }
+}
@@ -30,14 +30,16 @@ impl<'scene, S> geom::Surface<'scene> for Tree<'scene, S> where S: geom::Surface
         loop {
 
             if !self.0[this].bound().hit_any(ray) {
-                #[cfg(feature = "stats")]
-                crate::stats::BVH_MISSES.inc();
+                if crate::stats::enabled() {
+                    crate::stats::BVH_MISSES.inc();
+                }
                 pop!();
                 continue
             }
 
-            #[cfg(feature = "stats")]
-            crate::stats::BVH_HITS.inc();
+            if crate::stats::enabled() {
+                crate::stats::BVH_HITS.inc();
+            }
 
             match &self.0[this] {
             | Node::Leaf(leaf) => {
@@ -76,14 +78,16 @@ impl<'scene, S> geom::Surface<'scene> for Tree<'scene, S> where S: geom::Surface
         loop {
 
             if !self.0[this].bound().hit_any(ray) {
-                #[cfg(feature = "stats")]
-                crate::stats::BVH_MISSES.inc();
+                if crate::stats::enabled() {
+                    crate::stats::BVH_MISSES.inc();
+                }
                 pop!();
                 continue
             }
 
-            #[cfg(feature = "stats")]
-            crate::stats::BVH_HITS.inc();
+            if crate::stats::enabled() {
+                crate::stats::BVH_HITS.inc();
+            }
 
             match &self.0[this] {
             | Node::Leaf(leaf) => {
@@ -1,4 +1,5 @@
 use crate::math;
+use crate::sample;
 
 mod lambertian;
 mod specular;
@@ -30,8 +31,14 @@ pub struct Sample {
 
 pub trait BxDF: std::fmt::Debug + Send + Sync {
     fn eval(&self, wi: &math::Vec3, wr: &math::Vec3, n: &math::Vec3) -> math::Vec3;
-    fn sample(&self, d: &math::Vec3, n: &math::Vec3) -> Sample;
+    fn sample<S: sample::Sampler>(&self, d: &math::Vec3, n: &math::Vec3, sampler: &mut S) -> Sample;
+
+    /// Solid angle density of `sample` choosing `wr` given `wi`, excluding
+    /// delta lobes, which no other sampling strategy can ever produce.
     fn pdf(&self, wi: &math::Vec3, wr: &math::Vec3, n: &math::Vec3) -> f32;
+
+    /// Whether every lobe is a delta distribution, so that `eval` is always zero.
+    fn is_delta(&self) -> bool;
 }
 
 impl<'a, B> BxDF for &'a B where B: BxDF + ?Sized {
@@ -39,13 +46,17 @@ impl<'a, B> BxDF for &'a B where B: BxDF + ?Sized {
         (*self).eval(wi, wr, n)
     }
 
-    fn sample(&self, d: &math::Vec3, n: &math::Vec3) -> Sample {
-        (*self).sample(d, n)
+    fn sample<S: sample::Sampler>(&self, d: &math::Vec3, n: &math::Vec3, sampler: &mut S) -> Sample {
+        (*self).sample(d, n, sampler)
     }
 
     fn pdf(&self, wi: &math::Vec3, wr: &math::Vec3, n: &math::Vec3) -> f32 {
         (*self).pdf(wi, wr, n)
     }
+
+    fn is_delta(&self) -> bool {
+        (*self).is_delta()
+    }
 }
 
 #[derive(Copy, Clone, Debug)]
@@ -66,12 +77,12 @@ impl<'scene> BxDF for Any<'scene> {
         }
     }
 
-    fn sample(&self, d: &math::Vec3, n: &math::Vec3) -> Sample {
+    fn sample<S: sample::Sampler>(&self, d: &math::Vec3, n: &math::Vec3, sampler: &mut S) -> Sample {
         match self {
-            Any::Lambertian(bxdf) => bxdf.sample(d, n),
-            Any::Specular(bxdf) => bxdf.sample(d, n),
-            Any::Glazed(bxdf) => bxdf.sample(d, n),
-            Any::Mirror(bxdf) => bxdf.sample(d, n),
+            Any::Lambertian(bxdf) => bxdf.sample(d, n, sampler),
+            Any::Specular(bxdf) => bxdf.sample(d, n, sampler),
+            Any::Glazed(bxdf) => bxdf.sample(d, n, sampler),
+            Any::Mirror(bxdf) => bxdf.sample(d, n, sampler),
         }
     }
 
@@ -83,4 +94,13 @@ impl<'scene> BxDF for Any<'scene> {
             Any::Mirror(bxdf) => bxdf.pdf(wi, wr, n),
         }
     }
+
+    fn is_delta(&self) -> bool {
+        match self {
+            Any::Lambertian(bxdf) => bxdf.is_delta(),
+            Any::Specular(bxdf) => bxdf.is_delta(),
+            Any::Glazed(bxdf) => bxdf.is_delta(),
+            Any::Mirror(bxdf) => bxdf.is_delta(),
+        }
+    }
 }
@@ -1,5 +1,6 @@
 use crate::bxdf;
 use crate::math;
+use crate::sample;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Glazed<'scene> {
@@ -14,25 +15,33 @@ impl<'scene> Glazed<'scene> {
 }
 
 impl<'scene> bxdf::BxDF for Glazed<'scene> {
+    /// Light reaching the base through the glaze, which excludes whatever
+    /// the delta reflection off its surface took.
     fn eval(&self, wi: &math::Vec3, wr: &math::Vec3, n: &math::Vec3) -> math::Vec3 {
-        self.bxdf.eval(wi, wr, n)
+        let fresnel = bxdf::Fresnel::dieletric(wi, n, self.eta);
+        self.bxdf.eval(wi, wr, n) * (1.0 - fresnel.reflect)
     }
 
-    fn sample(&self, wi: &math::Vec3, n: &math::Vec3) -> bxdf::Sample {
+    fn sample<S: sample::Sampler>(&self, wi: &math::Vec3, n: &math::Vec3, sampler: &mut S) -> bxdf::Sample {
         let fresnel = bxdf::Fresnel::dieletric(wi, n, self.eta);
-        if rand::random::<f32>() <= fresnel.reflect {
+        if sampler.get_1d() <= fresnel.reflect {
             fresnel.reflect()
         } else {
-            let d = math::cosine_sphere();
+            let d = math::cosine_sphere(sampler);
             let (u, v) = math::basis(n);
             let wr = n * d.z() + u * d.x() + v * d.y();
             let v = self.bxdf.eval(wi, &wr, n) * (1.0 - fresnel.reflect);
-            let p = (1.0 - fresnel.reflect) * (fresnel.cos_i / math::PI);
+            let p = (1.0 - fresnel.reflect) * (d.z() / math::PI);
             bxdf::Sample { d: wr, v, p, delta: false }
         }
     }
 
-    fn pdf(&self, _: &math::Vec3, _: &math::Vec3, _: &math::Vec3) -> f32 {
-        0.0
+    fn pdf(&self, wi: &math::Vec3, wr: &math::Vec3, n: &math::Vec3) -> f32 {
+        let fresnel = bxdf::Fresnel::dieletric(wi, n, self.eta);
+        (1.0 - fresnel.reflect) * math::max(0.0, wr.dot(n)) / math::PI
+    }
+
+    fn is_delta(&self) -> bool {
+        self.bxdf.is_delta()
     }
 }
@@ -1,6 +1,7 @@
 use crate::bxdf;
 use crate::math;
 use crate::math::Vec3;
+use crate::sample;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Lambertian {
@@ -22,8 +23,8 @@ impl bxdf::BxDF for Lambertian {
         }
     }
 
-    fn sample(&self, _: &Vec3, n: &Vec3) -> bxdf::Sample {
-        let local = math::cosine_sphere();  
+    fn sample<S: sample::Sampler>(&self, _: &Vec3, n: &Vec3, sampler: &mut S) -> bxdf::Sample {
+        let local = math::cosine_sphere(sampler);  
         let (u, v) = math::basis(n);
         let d = (n * local.z() + u * local.x() + v * local.y()).normalize();
         bxdf::Sample {
@@ -37,4 +38,8 @@ impl bxdf::BxDF for Lambertian {
     fn pdf(&self, _: &Vec3, wr: &Vec3, n: &Vec3) -> f32 {
         if wr.dot(n) >= 0.0 { wr.dot(n) / math::PI } else { 0.0 }
     }
+
+    fn is_delta(&self) -> bool {
+        false
+    }
 }
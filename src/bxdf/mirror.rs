@@ -1,5 +1,6 @@
 use crate::bxdf;
 use crate::math;
+use crate::sample;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Mirror;
@@ -9,7 +10,7 @@ impl bxdf::BxDF for Mirror {
         math::Vec3::default()
     }
 
-    fn sample(&self, d: &math::Vec3, n: &math::Vec3) -> bxdf::Sample {
+    fn sample<S: sample::Sampler>(&self, d: &math::Vec3, n: &math::Vec3, _: &mut S) -> bxdf::Sample {
         let fresnel = bxdf::Fresnel::mirror(d, n);
         fresnel.reflect()
     }
@@ -17,4 +18,8 @@ impl bxdf::BxDF for Mirror {
     fn pdf(&self, _: &math::Vec3, _: &math::Vec3, _: &math::Vec3) -> f32 {
         0.0
     }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
 }
@@ -1,5 +1,6 @@
 use crate::bxdf;
 use crate::math;
+use crate::sample;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Specular {
@@ -18,9 +19,9 @@ impl bxdf::BxDF for Specular {
         math::Vec3::default()
     }
 
-    fn sample(&self, d: &math::Vec3, n: &math::Vec3) -> bxdf::Sample {
+    fn sample<S: sample::Sampler>(&self, d: &math::Vec3, n: &math::Vec3, sampler: &mut S) -> bxdf::Sample {
         let fresnel = bxdf::Fresnel::dieletric(d, n, self.eta);
-        if rand::random::<f32>() <= fresnel.reflect {
+        if sampler.get_1d() <= fresnel.reflect {
             fresnel.reflect()
         } else {
             fresnel.refract()
@@ -30,4 +31,8 @@ impl bxdf::BxDF for Specular {
     fn pdf(&self, _: &math::Vec3, _: &math::Vec3, _: &math::Vec3) -> f32 {
         0.0
     }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
 }
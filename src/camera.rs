@@ -1,4 +1,5 @@
 use crate::math;
+use crate::sample;
 
 /// Source of light rays.
 #[derive(Copy, Clone, Debug, Default)]
@@ -21,6 +22,12 @@ pub struct Camera {
     /// Normalized vertical axis
     v: math::Vec3,
 
+    /// Normalized axis pointing back out of the screen
+    w: math::Vec3,
+
+    /// Distance to the plane of focus
+    focus: f32,
+
     /// Lens radius
     lens: f32,
 }
@@ -51,21 +58,67 @@ impl Camera {
             origin,
             u,
             v,
+            w,
+            focus,
             lens: aperture / 2.0,
         }
     }
 
+    /// Camera looking from `pose.origin` toward `pose.toward`.
+    pub fn from_pose(pose: &Pose) -> Self {
+        Camera::new(
+            pose.origin,
+            pose.toward,
+            pose.up,
+            pose.fov,
+            pose.aspect,
+            pose.aperture,
+            pose.focus,
+        )
+    }
+
     /// Generate a random point within this camera's lens disk
-    fn random_offset(&self) -> math::Vec3 {
-        let d = math::uniform_disk();
+    fn random_offset<S: sample::Sampler>(&self, sampler: &mut S) -> math::Vec3 {
+        let d = math::uniform_disk(sampler);
         self.u * self.lens * d.x() +
         self.v * self.lens * d.y()
     }
 
+    /// Generate a random point on this camera's lens.
+    pub fn lens_point<S: sample::Sampler>(&self, sampler: &mut S) -> math::Vec3 {
+        self.origin + self.random_offset(sampler)
+    }
+
+    /// Normalized screen coordinates where a ray leaving lens point `p` in direction `d`
+    /// crosses the plane of focus, or `None` if it misses the image.
+    ///
+    /// This inverts `get`, for light paths that reach the lens on their own.
+    pub fn project(&self, p: &math::Vec3, d: &math::Vec3) -> Option<(f32, f32)> {
+        let cos = -d.dot(&self.w);
+        if cos <= 0.0 { return None }
+        let q = *p + *d * (self.focus / cos) - self.corner;
+        let u = q.dot(&self.horizontal) / self.horizontal.len_sq();
+        let v = q.dot(&self.vertical) / self.vertical.len_sq();
+        if (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v) {
+            Some((u, v))
+        } else {
+            None
+        }
+    }
+
+    /// Solid angle density of `get` casting direction `d` from lens point `p`,
+    /// when screen coordinates are spread uniformly over the whole image.
+    pub fn pdf(&self, p: &math::Vec3, d: &math::Vec3) -> f32 {
+        if self.project(p, d).is_none() { return 0.0 }
+        let cos = -d.dot(&self.w);
+        let area = self.horizontal.len() * self.vertical.len() / (self.focus * self.focus);
+        1.0 / (area * cos * cos * cos)
+    }
+
     /// Generate a ray through normalized screen coordinates `(u, v)`,
     /// where both `u` and `v` are in the range `[0.0, 1.0]`.
-    pub fn get(&self, u: f32, v: f32) -> math::Ray {
-        let offset = self.random_offset();
+    pub fn get<S: sample::Sampler>(&self, u: f32, v: f32, sampler: &mut S) -> math::Ray {
+        let offset = self.random_offset(sampler);
         math::Ray::new(
             self.origin + offset,
             self.corner + self.horizontal * u
@@ -75,3 +128,111 @@ impl Camera {
         )
     }
 }
+
+/// Placement and lens settings of a camera, as written in a scene file.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pose {
+    pub origin: math::Vec3,
+    pub toward: math::Vec3,
+    pub up: math::Vec3,
+
+    /// Vertical field of view, in degrees
+    pub fov: f32,
+    pub aspect: f32,
+    pub aperture: f32,
+    pub focus: f32,
+}
+
+impl Pose {
+    /// Blend between `self` at `t = 0.0` and `other` at `t = 1.0`.
+    pub fn lerp(&self, other: &Pose, t: f32) -> Pose {
+        let mix = |a: f32, b: f32| a + (b - a) * t;
+        Pose {
+            origin: self.origin + (other.origin - self.origin) * t,
+            toward: self.toward + (other.toward - self.toward) * t,
+            up: self.up + (other.up - self.up) * t,
+            fov: mix(self.fov, other.fov),
+            aspect: mix(self.aspect, other.aspect),
+            aperture: mix(self.aperture, other.aperture),
+            focus: mix(self.focus, other.focus),
+        }
+    }
+}
+
+/// Camera poses keyed by time in frames, interpolated linearly in between
+/// and held constant before the first and after the last.
+#[derive(Clone, Debug)]
+pub struct Track {
+    keys: Vec<(f32, Pose)>,
+
+    /// Camera at each key, so that still cameras are only set up once
+    cameras: Vec<Camera>,
+}
+
+impl Track {
+    /// Track that stays at `pose` for every frame.
+    pub fn fixed(pose: Pose) -> Self {
+        Track::new(vec![(0.0, pose)])
+    }
+
+    /// Track through `keys`, which need not be sorted.
+    pub fn new(mut keys: Vec<(f32, Pose)>) -> Self {
+        assert!(!keys.is_empty(), "[INTERNAL ERROR]: camera track requires at least one key");
+        keys.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let cameras = keys
+            .iter()
+            .map(|(_, pose)| Camera::from_pose(pose))
+            .collect();
+        Track { keys, cameras }
+    }
+
+    /// Replace the aspect ratio at every key.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        for (_, pose) in &mut self.keys {
+            pose.aspect = aspect;
+        }
+        self.cameras = self.keys
+            .iter()
+            .map(|(_, pose)| Camera::from_pose(pose))
+            .collect();
+    }
+
+    /// Camera at `time`.
+    pub fn at(&self, time: f32) -> Camera {
+        let next = self.keys.partition_point(|(key, _)| *key <= time);
+        match (next.checked_sub(1), self.keys.get(next)) {
+        | (Some(prev), None) => self.cameras[prev],
+        | (None, _) => self.cameras[0],
+        | (Some(prev), Some((t1, p1))) => {
+            let (t0, p0) = &self.keys[prev];
+            if time == *t0 { return self.cameras[prev] }
+            Camera::from_pose(&p0.lerp(p1, (time - t0) / (t1 - t0)))
+        }
+        }
+    }
+}
+
+/// Interval over which each frame gathers light, relative to the start of the frame.
+///
+/// Rays are spread uniformly over the interval, blurring anything that moves
+/// while it is open. A closed shutter takes every sample at the start of the frame.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Shutter {
+    pub open: f32,
+    pub close: f32,
+}
+
+impl Shutter {
+    pub fn new(open: f32, close: f32) -> Self {
+        Shutter { open, close }
+    }
+
+    /// Time at which to cast a ray for `frame`.
+    pub fn sample<S: sample::Sampler>(&self, frame: usize, sampler: &mut S) -> f32 {
+        let start = frame as f32 + self.open;
+        if self.close <= self.open {
+            return start
+        }
+        start + (self.close - self.open) * sampler.get_1d()
+    }
+}
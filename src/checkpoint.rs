@@ -0,0 +1,233 @@
+use std::fs;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::path;
+
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use byteorder::LE;
+
+use crate::adaptive;
+use crate::film;
+use crate::math;
+use crate::scene;
+
+const MAGIC: &[u8; 8] = b"PHOTONCK";
+const VERSION: u32 = 3;
+
+/// Saved state of an unfinished render.
+///
+/// Samplers derive every sample from the seed, pixel and sample index,
+/// so the seed and cursor are all the random state needed to continue.
+#[derive(Clone, Debug)]
+pub struct Checkpoint {
+    /// Fingerprint of the scene being rendered
+    pub hash: u64,
+
+    /// Random seed of the render
+    pub seed: u64,
+
+    /// Progress through the sample budget
+    pub cursor: scene::Cursor,
+
+    /// Accumulated radiance and per-pixel sample statistics
+    pub film: film::Film,
+}
+
+impl Checkpoint {
+    /// Read a checkpoint, failing unless it was saved from a render of `scene`.
+    pub fn load<P: AsRef<path::Path>>(path: P, scene: &scene::Scene) -> io::Result<Self> {
+        Self::read_from(io::BufReader::new(fs::File::open(path)?), Some(scene))
+    }
+
+    /// Write this checkpoint to `path`, replacing the old file only once the new one is complete.
+    pub fn save<P: AsRef<path::Path>>(&self, path: P) -> io::Result<()> {
+        let path = path.as_ref();
+        let mut temporary = path.as_os_str().to_owned();
+        temporary.push(".tmp");
+        let mut out = io::BufWriter::new(fs::File::create(&temporary)?);
+        self.write(&mut out)?;
+        out.into_inner()
+            .map_err(io::IntoInnerError::into_error)?
+            .sync_all()?;
+        fs::rename(&temporary, path)
+    }
+
+    pub fn write<W: Write>(&self, mut out: W) -> io::Result<()> {
+        out.write_all(MAGIC)?;
+        out.write_u32::<LE>(VERSION)?;
+        out.write_u64::<LE>(self.hash)?;
+        out.write_u64::<LE>(self.seed)?;
+        out.write_u64::<LE>(self.cursor.pass.start as u64)?;
+        out.write_u64::<LE>(self.cursor.pass.end as u64)?;
+        out.write_u64::<LE>(self.cursor.tile as u64)?;
+        out.write_u64::<LE>(self.film.width() as u64)?;
+        out.write_u64::<LE>(self.film.height() as u64)?;
+        for pixel in self.film.pixels() {
+            write_pixel(&mut out, pixel)?;
+        }
+        out.flush()
+    }
+
+    pub fn read<R: Read>(input: R) -> io::Result<Self> {
+        Self::read_from(input, None)
+    }
+
+    /// Read a checkpoint, checking the header against `scene` before reading any pixels.
+    fn read_from<R: Read>(mut input: R, scene: Option<&scene::Scene>) -> io::Result<Self> {
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(invalid("not a checkpoint file"))
+        }
+        if input.read_u32::<LE>()? != VERSION {
+            return Err(invalid("unsupported checkpoint version"))
+        }
+        let hash = input.read_u64::<LE>()?;
+        let seed = input.read_u64::<LE>()?;
+        let start = input.read_u64::<LE>()? as usize;
+        let end = input.read_u64::<LE>()? as usize;
+        let tile = input.read_u64::<LE>()? as usize;
+        let width = input.read_u64::<LE>()? as usize;
+        let height = input.read_u64::<LE>()? as usize;
+        if let Some(scene) = scene {
+            if hash != scene.hash() {
+                return Err(invalid("checkpoint was saved from a different scene"))
+            }
+            if (width, height) != (scene.width(), scene.height()) {
+                return Err(invalid("checkpoint size does not match the scene"))
+            }
+        }
+
+        // Grow with the pixels actually read, so a corrupt header cannot force a huge allocation
+        let count = width
+            .checked_mul(height)
+            .ok_or_else(|| invalid("checkpoint size is too large"))?;
+        let mut pixels = Vec::new();
+        for _ in 0..count {
+            pixels.push(read_pixel(&mut input)?);
+        }
+        Ok(Checkpoint {
+            hash,
+            seed,
+            cursor: scene::Cursor { pass: start..end, tile },
+            film: film::Film::from_pixels(width, height, pixels),
+        })
+    }
+}
+
+pub(crate) fn write_pixel<W: Write>(mut out: W, pixel: &film::Pixel) -> io::Result<()> {
+    let sum = pixel.sum();
+    out.write_f32::<LE>(sum.x())?;
+    out.write_f32::<LE>(sum.y())?;
+    out.write_f32::<LE>(sum.z())?;
+    out.write_f32::<LE>(pixel.weight())?;
+    let splat = pixel.splat();
+    out.write_f32::<LE>(splat.x())?;
+    out.write_f32::<LE>(splat.y())?;
+    out.write_f32::<LE>(splat.z())?;
+    write_estimate(out, &pixel.estimate())
+}
+
+pub(crate) fn read_pixel<R: Read>(mut input: R) -> io::Result<film::Pixel> {
+    let sum = math::Vec3::new(
+        input.read_f32::<LE>()?,
+        input.read_f32::<LE>()?,
+        input.read_f32::<LE>()?,
+    );
+    let weight = input.read_f32::<LE>()?;
+    let splat = math::Vec3::new(
+        input.read_f32::<LE>()?,
+        input.read_f32::<LE>()?,
+        input.read_f32::<LE>()?,
+    );
+    let estimate = read_estimate(input)?;
+    Ok(film::Pixel::new(sum, weight, splat, estimate))
+}
+
+pub(crate) fn write_estimate<W: Write>(mut out: W, estimate: &adaptive::Estimate) -> io::Result<()> {
+    out.write_u64::<LE>(estimate.count() as u64)?;
+    out.write_f32::<LE>(estimate.mean())?;
+    out.write_f32::<LE>(estimate.m2())
+}
+
+pub(crate) fn read_estimate<R: Read>(mut input: R) -> io::Result<adaptive::Estimate> {
+    let count = input.read_u64::<LE>()? as usize;
+    let mean = input.read_f32::<LE>()?;
+    let m2 = input.read_f32::<LE>()?;
+    Ok(adaptive::Estimate::new(count, mean, m2))
+}
+
+pub(crate) fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::arena;
+
+    fn checkpoint(hash: u64) -> Checkpoint {
+        let mut film = film::Film::new(3, 2);
+        film.add(0, 0, &math::Vec3::new(1.0, 2.0, 3.0), 0.5);
+        film.add(2, 1, &math::Vec3::new(4.0, 5.0, 6.0), 2.0);
+        film.pixel_mut(1, 1).record(&adaptive::Estimate::new(4, 0.25, 0.125));
+        Checkpoint {
+            hash,
+            seed: 7,
+            cursor: scene::Cursor { pass: 8..16, tile: 5 },
+            film,
+        }
+    }
+
+    fn bytes(checkpoint: &Checkpoint) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        checkpoint.write(&mut bytes).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn round_trip() {
+        let saved = checkpoint(42);
+        let read = Checkpoint::read(bytes(&saved).as_slice()).unwrap();
+        assert_eq!((read.hash, read.seed), (42, 7));
+        assert_eq!(read.cursor, saved.cursor);
+        assert_eq!((read.film.width(), read.film.height()), (3, 2));
+        for (read, saved) in read.film.pixels().iter().zip(saved.film.pixels()) {
+            let (r, s) = (read.sum(), saved.sum());
+            assert_eq!((r.x(), r.y(), r.z()), (s.x(), s.y(), s.z()));
+            let (r, s) = (read.splat(), saved.splat());
+            assert_eq!((r.x(), r.y(), r.z()), (s.x(), s.y(), s.z()));
+            assert_eq!(read.weight(), saved.weight());
+            assert_eq!(read.samples(), saved.samples());
+            assert_eq!(read.estimate().mean(), saved.estimate().mean());
+            assert_eq!(read.estimate().m2(), saved.estimate().m2());
+        }
+    }
+
+    #[test]
+    fn rejects_different_scene() {
+        let arena = arena::Arena::default();
+        let source = b"width 3\nheight 2\ncamera 0 0 5 0 0 0 0 1 0 45 1.5 0 1\nsurface sphere 0 0 0 1 lambertian 0.5 0.5 0.5\n";
+        let scene = scene::Parser::new(&arena, scene::Lexer::new(&source[..]))
+            .parse_scene()
+            .unwrap();
+        let bytes = bytes(&checkpoint(scene.hash() ^ 1));
+        let error = Checkpoint::read_from(bytes.as_slice(), Some(&scene)).unwrap_err();
+        assert_eq!(error.to_string(), "checkpoint was saved from a different scene");
+
+        let bytes = self::bytes(&checkpoint(scene.hash()));
+        assert!(Checkpoint::read_from(bytes.as_slice(), Some(&scene)).is_ok());
+    }
+
+    #[test]
+    fn rejects_overflowing_size() {
+        let mut bytes = bytes(&checkpoint(0));
+        let width = MAGIC.len() + 4 + 8 * 5;
+        bytes[width..width + 8].copy_from_slice(&u64::MAX.to_le_bytes());
+        let error = Checkpoint::read(bytes.as_slice()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+}
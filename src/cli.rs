@@ -0,0 +1,390 @@
+use std::fmt;
+use std::path;
+use std::str;
+
+use photon::image;
+use photon::remote;
+
+pub const USAGE: &str = "\
+Usage: photon [OPTIONS] <SCENE>
+       photon serve [--listen <ADDRESS>] [--threads <COUNT>]
+
+Options:
+    -o, --output <PATH>        Output image (.png, .exr, .hdr, .pfm) [default: out.png]
+        --width <PIXELS>       Override image width, and the camera aspect ratio to match
+        --height <PIXELS>      Override image height, and the camera aspect ratio to match
+    -s, --samples <COUNT>      Override samples per pixel
+    -i, --integrator <SPEC>    Override integrator, e.g. \"path 10 1.0\"
+        --tonemap <SPEC>       Override tone mapping: clamp, reinhard, extended <WHITE> or aces
+        --exposure <STOPS>     Override exposure adjustment
+        --transfer <SPEC>      Override display encoding: srgb or gamma <EXPONENT>
+        --sampler <KIND>       Override sample generator: independent, stratified or sobol
+        --seed <SEED>          Override random seed; equal seeds give identical images
+        --adaptive <SPEC>      Stop sampling converged pixels: \"<MIN-SAMPLES> <THRESHOLD>\"
+        --heatmap <PATH>       Also write per-pixel sample counts as a false color image
+        --crop <SPEC>          Only render a window: \"X0 Y0 X1 Y1\" in pixels or \"normalized X0 Y0 X1 Y1\"
+        --merge <IMAGE>        Paste the cropped render into IMAGE instead of writing the crop alone;
+                               IMAGE must be .png or .pfm, like the output
+        --filter <SPEC>        Override pixel filter, e.g. \"gaussian 1.5 2.0\" or \"mitchell 2.0 0.33 0.33\"
+        --progressive          Render in passes of 1, 2, 4, ... samples, updating the output after each
+        --checkpoint <SECONDS> Update the output at most this often instead; implies --progressive
+        --save <PATH>          Save resumable render state alongside every output image
+        --resume <PATH>        Continue the render saved in PATH, keeping its seed
+        --tiles <SPEC>         Override tile size and order, e.g. \"32 hilbert\", \"64 spiral\" or \"16 scanline\"
+        --camera <NAME>        Only render the camera with this name
+        --shutter <SPEC>       Override motion blur interval within each frame: \"<OPEN> <CLOSE>\"
+        --frames <RANGE>       Only render these frames of an animation, e.g. \"12\" or \"1-24\"
+        --workers <ADDRESSES>  Render on `photon serve` workers instead, e.g. \"127.0.0.1:7700,127.0.0.1:7701\"
+    -j, --threads <COUNT>      Number of render threads [default: one per core]
+        --[no-]progress        Show a progress bar while rendering
+        --[no-]stats           Print intersection statistics after rendering
+        --help                 Print this message
+
+Serve options:
+        --listen <ADDRESS>     Address to accept coordinators on [default: 127.0.0.1:7700]
+";
+
+/// Mode of operation selected on the command line.
+#[derive(Clone, Debug)]
+pub enum Command {
+    /// Render a scene
+    Render(Options),
+
+    /// Render tiles on behalf of remote coordinators
+    Serve(Server),
+}
+
+impl Command {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut args = args.into_iter().peekable();
+        match args.peek().map(String::as_str) {
+        | Some("serve") => {
+            args.next();
+            Server::parse(args).map(Command::Serve)
+        }
+        | _ => Options::parse(args).map(Command::Render),
+        }
+    }
+}
+
+/// Command-line options for `photon serve`.
+#[derive(Clone, Debug)]
+pub struct Server {
+    /// Address to listen on
+    pub address: String,
+
+    /// Size of the render thread pool
+    pub threads: Option<usize>,
+}
+
+impl Server {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut server = Server {
+            address: String::from(remote::ADDRESS),
+            threads: None,
+        };
+
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let (option, inline) = match arg.find('=') {
+            | Some(index) if arg.starts_with("--") => (arg[..index].to_owned(), Some(arg[index + 1..].to_owned())),
+            | _ => (arg, None),
+            };
+
+            let mut value = || inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| Error::Missing(option.clone()));
+
+            match option.as_str() {
+            | "--listen" => server.address = value()?,
+            | "-j" | "--threads" => server.threads = Some(parse(&option, value()?)?),
+            | "--help" => return Err(Error::Help),
+            | _ => return Err(Error::Unknown(option)),
+            }
+        }
+
+        Ok(server)
+    }
+}
+
+/// Command-line options for the `photon` binary.
+#[derive(Clone, Debug)]
+pub struct Options {
+    /// Scene description file
+    pub scene: path::PathBuf,
+
+    /// Output image
+    pub output: path::PathBuf,
+
+    /// Image width override
+    pub width: Option<usize>,
+
+    /// Image height override
+    pub height: Option<usize>,
+
+    /// Samples per pixel override
+    pub samples: Option<usize>,
+
+    /// Integrator override, in scene file syntax
+    pub integrator: Option<String>,
+
+    /// Tone mapping operator override, in scene file syntax
+    pub tonemap: Option<String>,
+
+    /// Exposure override
+    pub exposure: Option<f32>,
+
+    /// Transfer function override, in scene file syntax
+    pub transfer: Option<String>,
+
+    /// Sample generator override, in scene file syntax
+    pub sampler: Option<String>,
+
+    /// Random seed override
+    pub seed: Option<u64>,
+
+    /// Adaptive sampling override, in scene file syntax
+    pub adaptive: Option<String>,
+
+    /// Sample count heatmap output
+    pub heatmap: Option<path::PathBuf>,
+
+    /// Crop window override, in scene file syntax
+    pub crop: Option<String>,
+
+    /// Full-size image to paste the crop window into
+    pub merge: Option<path::PathBuf>,
+
+    /// Reconstruction filter override, in scene file syntax
+    pub filter: Option<String>,
+
+    /// Whether to render in passes of increasing sample counts
+    pub progressive: bool,
+
+    /// Minimum time between intermediate images, in seconds
+    pub checkpoint: Option<f32>,
+
+    /// Render state output
+    pub save: Option<path::PathBuf>,
+
+    /// Render state to continue from
+    pub resume: Option<path::PathBuf>,
+
+    /// Tile scheduling override, in scene file syntax
+    pub tiles: Option<String>,
+
+    /// Name of the only camera to render
+    pub camera: Option<String>,
+
+    /// Shutter interval override, in scene file syntax
+    pub shutter: Option<String>,
+
+    /// First and last frame to render
+    pub frames: Option<(usize, usize)>,
+
+    /// Addresses of remote workers to render on
+    pub workers: Vec<String>,
+
+    /// Size of the render thread pool
+    pub threads: Option<usize>,
+
+    /// Whether to display a progress bar
+    pub progress: bool,
+
+    /// Whether to record and print statistics
+    pub stats: bool,
+}
+
+#[derive(Debug)]
+pub enum Error {
+    /// User asked for the usage message
+    Help,
+
+    /// No scene file was given
+    Scene,
+
+    /// Option requires a value, but none was given
+    Missing(String),
+
+    /// Option value could not be parsed
+    Invalid { option: String, value: String },
+
+    /// Unrecognized option or extra positional argument
+    Unknown(String),
+}
+
+impl Options {
+    pub fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Self, Error> {
+        let mut scene = None;
+        let mut options = Options {
+            scene: path::PathBuf::default(),
+            output: path::PathBuf::from("out.png"),
+            width: None,
+            height: None,
+            samples: None,
+            integrator: None,
+            tonemap: None,
+            exposure: None,
+            transfer: None,
+            sampler: None,
+            seed: None,
+            adaptive: None,
+            heatmap: None,
+            crop: None,
+            merge: None,
+            filter: None,
+            progressive: false,
+            checkpoint: None,
+            save: None,
+            resume: None,
+            tiles: None,
+            camera: None,
+            shutter: None,
+            frames: None,
+            workers: Vec::new(),
+            threads: None,
+            progress: cfg!(feature = "progress"),
+            stats: cfg!(feature = "stats"),
+        };
+
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+
+            // Support both `--option value` and `--option=value`
+            let (option, inline) = match arg.find('=') {
+            | Some(index) if arg.starts_with("--") => (arg[..index].to_owned(), Some(arg[index + 1..].to_owned())),
+            | _ => (arg, None),
+            };
+
+            let mut value = || inline
+                .clone()
+                .or_else(|| args.next())
+                .ok_or_else(|| Error::Missing(option.clone()));
+
+            match option.as_str() {
+            | "-o" | "--output" => options.output = path::PathBuf::from(value()?),
+            | "--width" => options.width = Some(parse_positive(&option, value()?)?),
+            | "--height" => options.height = Some(parse_positive(&option, value()?)?),
+            | "-s" | "--samples" => options.samples = Some(parse_positive(&option, value()?)?),
+            | "-i" | "--integrator" => options.integrator = Some(value()?),
+            | "--tonemap" => options.tonemap = Some(value()?),
+            | "--exposure" => options.exposure = Some(parse(&option, value()?)?),
+            | "--transfer" => options.transfer = Some(value()?),
+            | "--sampler" => options.sampler = Some(value()?),
+            | "--seed" => options.seed = Some(parse(&option, value()?)?),
+            | "--adaptive" => options.adaptive = Some(value()?),
+            | "--heatmap" => options.heatmap = Some(path::PathBuf::from(value()?)),
+            | "--crop" => options.crop = Some(value()?),
+            | "--merge" => options.merge = Some(path::PathBuf::from(value()?)),
+            | "--filter" => options.filter = Some(value()?),
+            | "--progressive" => options.progressive = true,
+            | "--checkpoint" => {
+                let seconds: f32 = parse(&option, value()?)?;
+                if !seconds.is_finite() || seconds < 0.0 {
+                    return Err(Error::Invalid { option, value: seconds.to_string() })
+                }
+                options.checkpoint = Some(seconds);
+                options.progressive = true;
+            }
+            | "--save" => options.save = Some(path::PathBuf::from(value()?)),
+            | "--resume" => options.resume = Some(path::PathBuf::from(value()?)),
+            | "--tiles" => options.tiles = Some(value()?),
+            | "--camera" => options.camera = Some(value()?),
+            | "--shutter" => options.shutter = Some(value()?),
+            | "--frames" => {
+                let range = value()?;
+                let (first, last) = match range.split_once('-') {
+                | Some((first, last)) => (parse(&option, first.to_owned())?, parse(&option, last.to_owned())?),
+                | None => {
+                    let frame = parse(&option, range.clone())?;
+                    (frame, frame)
+                }
+                };
+                if first == 0 || first > last {
+                    return Err(Error::Invalid { option, value: range })
+                }
+                options.frames = Some((first, last));
+            }
+            | "--workers" => {
+                options.workers = value()?
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|address| !address.is_empty())
+                    .map(String::from)
+                    .collect();
+            }
+            | "-j" | "--threads" => options.threads = Some(parse(&option, value()?)?),
+            | "--progress" => options.progress = true,
+            | "--no-progress" => options.progress = false,
+            | "--stats" => options.stats = true,
+            | "--no-stats" => options.stats = false,
+            | "--help" => return Err(Error::Help),
+            | _ if option.starts_with('-') => return Err(Error::Unknown(option)),
+            | _ if scene.is_none() => scene = Some(path::PathBuf::from(option)),
+            | _ => return Err(Error::Unknown(option)),
+            }
+        }
+
+        if image::Format::from_path(&options.output).is_none() {
+            return Err(Error::Invalid {
+                option: String::from("--output"),
+                value: options.output.display().to_string(),
+            })
+        }
+
+        // Merging reads the base image back, and writes it out again in the same format
+        if let Some(merge) = &options.merge {
+            let format = image::Format::from_path(merge);
+            if !format.is_some_and(|format| format.is_readable())
+            || format != image::Format::from_path(&options.output) {
+                return Err(Error::Invalid {
+                    option: String::from("--merge"),
+                    value: merge.display().to_string(),
+                })
+            }
+        }
+
+        if let Some(heatmap) = &options.heatmap {
+            if image::Format::from_path(heatmap).is_none() {
+                return Err(Error::Invalid {
+                    option: String::from("--heatmap"),
+                    value: heatmap.display().to_string(),
+                })
+            }
+        }
+
+        options.scene = scene.ok_or(Error::Scene)?;
+        Ok(options)
+    }
+}
+
+fn parse<T: str::FromStr>(option: &str, value: String) -> Result<T, Error> {
+    value.parse::<T>().map_err(|_| Error::Invalid {
+        option: option.to_owned(),
+        value,
+    })
+}
+
+fn parse_positive(option: &str, value: String) -> Result<usize, Error> {
+    match value.parse::<usize>() {
+    | Ok(count) if count > 0 => Ok(count),
+    | _ => Err(Error::Invalid { option: option.to_owned(), value }),
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+        | Error::Help => write!(fmt, "{}", USAGE),
+        | Error::Scene => write!(fmt, "missing scene file"),
+        | Error::Missing(option) => write!(fmt, "missing value for '{}'", option),
+        | Error::Invalid { option, value } => write!(fmt, "invalid value '{}' for '{}'", value, option),
+        | Error::Unknown(arg) => write!(fmt, "unexpected argument '{}'", arg),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
@@ -0,0 +1,563 @@
+use std::collections::BTreeMap;
+use std::io;
+
+use rayon::prelude::*;
+
+use crate::prelude::*;
+use crate::adaptive;
+use crate::filter;
+use crate::math;
+
+/// Weighted sum of radiance samples landing in a single pixel.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Pixel {
+    /// Weighted radiance
+    sum: math::Vec3,
+
+    /// Total weight
+    weight: f32,
+
+    /// Radiance that light paths carried straight to this pixel,
+    /// to be divided by the number of light paths traced over the whole image
+    splat: math::Vec3,
+
+    /// Luminance statistics of samples taken within this pixel
+    estimate: adaptive::Estimate,
+}
+
+impl Pixel {
+    pub fn new(sum: math::Vec3, weight: f32, splat: math::Vec3, estimate: adaptive::Estimate) -> Self {
+        Pixel { sum, weight, splat, estimate }
+    }
+
+    pub fn add(&mut self, radiance: &math::Vec3, weight: f32) {
+        self.sum += radiance * weight;
+        self.weight += weight;
+    }
+
+    pub fn merge(&mut self, rhs: &Pixel) {
+        self.sum += rhs.sum;
+        self.weight += rhs.weight;
+        self.splat += rhs.splat;
+        self.estimate.merge(&rhs.estimate);
+    }
+
+    /// Fold in statistics of more samples taken within this pixel.
+    ///
+    /// Kept separately from `add`, because filtering spreads
+    /// each sample's radiance over several pixels.
+    pub fn record(&mut self, estimate: &adaptive::Estimate) {
+        self.estimate.merge(estimate);
+    }
+
+    pub fn sum(&self) -> math::Vec3 {
+        self.sum
+    }
+
+    pub fn weight(&self) -> f32 {
+        self.weight
+    }
+
+    pub fn splat(&self) -> math::Vec3 {
+        self.splat
+    }
+
+    /// Number of samples taken within this pixel.
+    pub fn samples(&self) -> usize {
+        self.estimate.count()
+    }
+
+    pub fn estimate(&self) -> adaptive::Estimate {
+        self.estimate
+    }
+
+    /// Weighted average radiance, or black if no samples have landed.
+    ///
+    /// Leaves out splats, which only the whole film knows how to scale.
+    pub fn resolve(&self) -> math::Vec3 {
+        if self.weight > 0.0 {
+            self.sum / self.weight
+        } else {
+            math::Vec3::default()
+        }
+    }
+}
+
+/// Radiance that a light path carried straight to pixel `(x, y)`,
+/// bypassing the reconstruction filter.
+#[derive(Copy, Clone, Debug)]
+pub struct Splat {
+    pub x: usize,
+    pub y: usize,
+    pub radiance: math::Vec3,
+}
+
+impl Splat {
+    pub fn new(x: usize, y: usize, radiance: math::Vec3) -> Self {
+        Splat { x, y, radiance }
+    }
+}
+
+/// Rectangle of pixels `[x0, x1) x [y0, y1)`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bounds {
+    pub x0: usize,
+    pub y0: usize,
+    pub x1: usize,
+    pub y1: usize,
+}
+
+impl Bounds {
+    pub fn new(x0: usize, y0: usize, x1: usize, y1: usize) -> Self {
+        Bounds { x0, y0, x1, y1 }
+    }
+
+    pub fn width(&self) -> usize {
+        self.x1.saturating_sub(self.x0)
+    }
+
+    pub fn height(&self) -> usize {
+        self.y1.saturating_sub(self.y0)
+    }
+
+    /// Grow by `pad` pixels on every side, without extending past `limit`.
+    pub fn expand(&self, pad: usize, limit: &Bounds) -> Self {
+        Bounds {
+            x0: self.x0.saturating_sub(pad).max(limit.x0),
+            y0: self.y0.saturating_sub(pad).max(limit.y0),
+            x1: (self.x1 + pad).min(limit.x1),
+            y1: (self.y1 + pad).min(limit.y1),
+        }
+    }
+}
+
+/// Window of the image to render, either in pixels or as fractions of the image size.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum Crop {
+    Pixels(Bounds),
+    Normalized { x0: f32, y0: f32, x1: f32, y1: f32 },
+}
+
+impl Crop {
+    /// Pixels covered by this window within a `width x height` image.
+    ///
+    /// Normalized windows include every pixel they partially overlap.
+    pub fn resolve(&self, width: usize, height: usize) -> Bounds {
+        let bounds = match *self {
+        | Crop::Pixels(bounds) => bounds,
+        | Crop::Normalized { x0, y0, x1, y1 } => Bounds::new(
+            (math::clamp(x0, 0.0, 1.0) * width as f32).floor() as usize,
+            (math::clamp(y0, 0.0, 1.0) * height as f32).floor() as usize,
+            (math::clamp(x1, 0.0, 1.0) * width as f32).ceil() as usize,
+            (math::clamp(y1, 0.0, 1.0) * height as f32).ceil() as usize,
+        ),
+        };
+        Bounds {
+            x0: bounds.x0.min(width),
+            y0: bounds.y0.min(height),
+            x1: bounds.x1.min(width).max(bounds.x0.min(width)),
+            y1: bounds.y1.min(height).max(bounds.y0.min(height)),
+        }
+    }
+}
+
+/// Accumulates linear radiance for an image, stored row-major with `y = 0` at the top.
+#[derive(Clone, Debug)]
+pub struct Film {
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+
+    /// Light paths traced over the whole image, one per sample,
+    /// including samples in pixels that a crop has since cut away
+    paths: usize,
+}
+
+impl Film {
+    pub fn new(width: usize, height: usize) -> Self {
+        Film {
+            width,
+            height,
+            pixels: vec![Pixel::default(); width * height],
+            paths: 0,
+        }
+    }
+
+    /// Restore a film from a row-major buffer of `width * height` pixels.
+    pub fn from_pixels(width: usize, height: usize, pixels: Vec<Pixel>) -> Self {
+        assert!(
+            pixels.len() == width * height,
+            "[INTERNAL ERROR]: pixel buffer does not match film dimensions",
+        );
+        let paths = pixels.iter().map(Pixel::samples).sum();
+        Film { width, height, pixels, paths }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixel(&self, x: usize, y: usize) -> &Pixel {
+        &self.pixels[y * self.width + x]
+    }
+
+    pub fn pixel_mut(&mut self, x: usize, y: usize) -> &mut Pixel {
+        &mut self.pixels[y * self.width + x]
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    /// Record a radiance sample at pixel `(x, y)`.
+    pub fn add(&mut self, x: usize, y: usize, radiance: &math::Vec3, weight: f32) {
+        self.pixel_mut(x, y).add(radiance, weight);
+    }
+
+    /// Resolved radiance at pixel `(x, y)`.
+    pub fn get(&self, x: usize, y: usize) -> math::Vec3 {
+        self.resolve_pixel(self.pixel(x, y))
+    }
+
+    /// Filtered average radiance of `pixel`, plus its share of the light path splats.
+    fn resolve_pixel(&self, pixel: &Pixel) -> math::Vec3 {
+        if self.paths > 0 {
+            pixel.resolve() + pixel.splat / self.paths as f32
+        } else {
+            pixel.resolve()
+        }
+    }
+
+    /// Accumulate all samples from another film of the same size.
+    pub fn merge(&mut self, rhs: &Film) {
+        assert!(
+            self.width == rhs.width && self.height == rhs.height,
+            "[INTERNAL ERROR]: cannot merge films of different sizes",
+        );
+        self.pixels
+            .iter_mut()
+            .zip(&rhs.pixels)
+            .for_each(|(lhs, rhs)| lhs.merge(rhs));
+        self.paths += rhs.paths;
+    }
+
+    /// Every pixel of this film.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::new(0, 0, self.width, self.height)
+    }
+
+    /// Copy of the pixels within `bounds`, as a film of their own.
+    pub fn crop(&self, bounds: &Bounds) -> Film {
+        let pixels = (bounds.y0..bounds.y1)
+            .flat_map(|y| &self.pixels[y * self.width + bounds.x0..y * self.width + bounds.x1])
+            .copied()
+            .collect();
+        Film {
+            paths: self.paths,
+            ..Film::from_pixels(bounds.width(), bounds.height(), pixels)
+        }
+    }
+
+    /// Resolve every pixel into a row-major buffer of radiance.
+    pub fn resolve(&self) -> Vec<math::Vec3> {
+        self.pixels
+            .par_iter()
+            .map(|pixel| self.resolve_pixel(pixel))
+            .collect()
+    }
+
+    /// Iterate over rows in parallel, from top to bottom.
+    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = &mut [Pixel]> {
+        self.pixels.par_chunks_mut(self.width)
+    }
+
+    /// Accumulate all samples from a tile of this film.
+    pub fn merge_tile(&mut self, tile: &Tile) {
+        assert!(
+            tile.x + tile.width <= self.width && tile.y + tile.height <= self.height,
+            "[INTERNAL ERROR]: tile lies outside of film",
+        );
+        for (row, pixels) in tile.pixels.chunks(tile.width.max(1)).enumerate() {
+            let start = (tile.y + row) * self.width + tile.x;
+            self.pixels[start..start + tile.width]
+                .iter_mut()
+                .zip(pixels)
+                .for_each(|(lhs, rhs)| lhs.merge(rhs));
+        }
+        for splat in &tile.splats {
+            self.pixel_mut(splat.x, splat.y).splat += splat.radiance;
+        }
+        self.paths += tile.pixels.iter().map(Pixel::samples).sum::<usize>();
+    }
+}
+
+/// Rectangular window onto a film, accumulated independently and merged back later.
+///
+/// Coordinates are given in the parent film's pixel space.
+#[derive(Clone, Debug)]
+pub struct Tile {
+    x: usize,
+    y: usize,
+    width: usize,
+    height: usize,
+    pixels: Vec<Pixel>,
+
+    /// Light path splats from this tile's samples, which may land anywhere on the film
+    splats: Vec<Splat>,
+}
+
+impl Tile {
+    /// Empty tile covering `width x height` pixels starting at `(x, y)`.
+    pub fn new(x: usize, y: usize, width: usize, height: usize) -> Self {
+        Tile {
+            x,
+            y,
+            width,
+            height,
+            pixels: vec![Pixel::default(); width * height],
+            splats: Vec::new(),
+        }
+    }
+
+    /// Tile covering `width x height` pixels starting at `(x, y)`, in row-major order.
+    pub fn from_pixels(x: usize, y: usize, width: usize, height: usize, pixels: Vec<Pixel>) -> Self {
+        assert!(
+            pixels.len() == width * height,
+            "[INTERNAL ERROR]: tile pixel count does not match dimensions",
+        );
+        Tile { x, y, width, height, pixels, splats: Vec::new() }
+    }
+
+    pub fn x(&self) -> usize {
+        self.x
+    }
+
+    pub fn y(&self) -> usize {
+        self.y
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[Pixel] {
+        &self.pixels
+    }
+
+    pub fn splats(&self) -> &[Splat] {
+        &self.splats
+    }
+
+    /// Keep light path splats, in order, for merging into the film along with this tile.
+    pub fn add_splats<I: IntoIterator<Item = Splat>>(&mut self, splats: I) {
+        self.splats.extend(splats);
+    }
+
+    /// Fold in statistics of more samples taken within film pixel `(x, y)`.
+    pub fn record(&mut self, x: usize, y: usize, estimate: &adaptive::Estimate) {
+        self.pixels[(y - self.y) * self.width + x - self.x].record(estimate);
+    }
+
+    /// Record a radiance sample at continuous film position `(x, y)`,
+    /// weighting every pixel center within the filter's support.
+    ///
+    /// Pixels outside of this tile are skipped.
+    pub fn splat(&mut self, filter: &filter::Any, x: f32, y: f32, radiance: &math::Vec3) {
+        let radius = filter.radius();
+        let x0 = math::max((x - 0.5 - radius).ceil(), self.x as f32) as usize;
+        let y0 = math::max((y - 0.5 - radius).ceil(), self.y as f32) as usize;
+        let x1 = math::min((x - 0.5 + radius).floor() + 1.0, (self.x + self.width) as f32);
+        let y1 = math::min((y - 0.5 + radius).floor() + 1.0, (self.y + self.height) as f32);
+        for py in y0..math::max(y1, 0.0) as usize {
+            for px in x0..math::max(x1, 0.0) as usize {
+                let weight = filter.eval(px as f32 + 0.5 - x, py as f32 + 0.5 - y);
+                if weight == 0.0 { continue }
+                self.pixels[(py - self.y) * self.width + px - self.x].add(radiance, weight);
+            }
+        }
+    }
+}
+
+/// Merges numbered tiles into a film in ascending order, whatever order they finish in.
+///
+/// Floating point addition is not associative, so merging in a fixed order
+/// keeps renders bit-for-bit reproducible across thread counts and schedules.
+#[derive(Debug)]
+pub struct Ordered<'film> {
+    film: &'film mut Film,
+    next: usize,
+    pending: BTreeMap<usize, Tile>,
+}
+
+impl<'film> Ordered<'film> {
+    /// Merger that expects tiles numbered from `first` upward.
+    pub fn new(film: &'film mut Film, first: usize) -> Self {
+        Ordered { film, next: first, pending: BTreeMap::new() }
+    }
+
+    /// Index of the next tile to be merged.
+    pub fn next(&self) -> usize {
+        self.next
+    }
+
+    pub fn film(&self) -> &Film {
+        self.film
+    }
+
+    /// Hand over the `index`-th tile, merging it and any tiles queued behind it.
+    pub fn push(&mut self, index: usize, tile: Tile) {
+        self.pending.insert(index, tile);
+        while let Some(tile) = self.pending.remove(&self.next) {
+            self.film.merge_tile(&tile);
+            self.next += 1;
+        }
+    }
+}
+
+/// Destination for a finished film.
+pub trait Sink {
+    fn write(&self, film: &Film) -> io::Result<()>;
+}
+
+impl<S> Sink for &S where S: Sink + ?Sized {
+    fn write(&self, film: &Film) -> io::Result<()> {
+        (*self).write(film)
+    }
+}
+
+impl<S> Sink for Box<S> where S: Sink + ?Sized {
+    fn write(&self, film: &Film) -> io::Result<()> {
+        (**self).write(film)
+    }
+}
+
+/// Sink adapter that only passes on the pixels within `bounds`.
+#[derive(Clone, Debug)]
+pub struct Cropped<S> {
+    sink: S,
+    bounds: Bounds,
+}
+
+impl<S> Cropped<S> {
+    pub fn new(sink: S, bounds: Bounds) -> Self {
+        Cropped { sink, bounds }
+    }
+}
+
+impl<S> Sink for Cropped<S> where S: Sink {
+    fn write(&self, film: &Film) -> io::Result<()> {
+        self.sink.write(&film.crop(&self.bounds))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rgb(v: math::Vec3) -> (f32, f32, f32) {
+        (v.x(), v.y(), v.z())
+    }
+
+    #[test]
+    fn add_resolves_to_weighted_average() {
+        let mut film = Film::new(2, 2);
+        film.add(1, 0, &math::Vec3::new(1.0, 2.0, 3.0), 1.0);
+        film.add(1, 0, &math::Vec3::new(3.0, 2.0, 1.0), 3.0);
+        assert_eq!(rgb(film.get(1, 0)), (2.5, 2.0, 1.5));
+        assert_eq!(rgb(film.get(0, 0)), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn merge_accumulates_samples() {
+        let mut lhs = Film::new(2, 1);
+        let mut rhs = Film::new(2, 1);
+        lhs.add(0, 0, &math::Vec3::broadcast(1.0), 1.0);
+        rhs.add(0, 0, &math::Vec3::broadcast(4.0), 1.0);
+        rhs.add(1, 0, &math::Vec3::broadcast(2.0), 0.5);
+        lhs.merge(&rhs);
+        assert_eq!(lhs.pixel(0, 0).weight(), 2.0);
+        assert_eq!(rgb(lhs.get(0, 0)), (2.5, 2.5, 2.5));
+        assert_eq!(rgb(lhs.get(1, 0)), (2.0, 2.0, 2.0));
+    }
+
+    #[test]
+    #[should_panic]
+    fn merge_rejects_different_sizes() {
+        Film::new(2, 1).merge(&Film::new(1, 2));
+    }
+
+    #[test]
+    fn resolve_is_row_major() {
+        let mut film = Film::new(2, 2);
+        film.add(1, 0, &math::Vec3::broadcast(1.0), 1.0);
+        film.add(0, 1, &math::Vec3::broadcast(2.0), 1.0);
+        let resolved = film.resolve().into_iter().map(|v| v.x()).collect::<Vec<_>>();
+        assert_eq!(resolved, vec![0.0, 1.0, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn splats_are_shared_over_every_path() {
+        let mut tile = Tile::new(0, 0, 2, 1);
+        let mut estimate = adaptive::Estimate::default();
+        estimate.add(0.0);
+        estimate.add(0.0);
+        tile.record(0, 0, &estimate);
+        tile.record(1, 0, &estimate);
+        tile.add_splats(vec![Splat::new(1, 0, math::Vec3::broadcast(8.0))]);
+        let mut film = Film::new(2, 1);
+        film.merge_tile(&tile);
+        assert_eq!(rgb(film.get(1, 0)), (2.0, 2.0, 2.0));
+        assert_eq!(rgb(film.get(0, 0)), (0.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn tiles_merge_at_their_offset() {
+        let filter = filter::Any::default();
+        let mut tile = Tile::new(1, 1, 2, 1);
+        tile.splat(&filter, 2.5, 1.5, &math::Vec3::broadcast(1.0));
+        let mut film = Film::new(3, 2);
+        film.merge_tile(&tile);
+        assert_eq!(film.pixel(2, 1).weight(), 1.0);
+        assert_eq!(film.pixels().iter().map(Pixel::weight).sum::<f32>(), 1.0);
+    }
+
+    #[test]
+    fn ordered_merges_in_index_order() {
+        let mut film = Film::new(1, 1);
+        let mut ordered = Ordered::new(&mut film, 0);
+        for index in [2, 0, 1] {
+            let mut tile = Tile::new(0, 0, 1, 1);
+            tile.splat(&filter::Any::default(), 0.5, 0.5, &math::Vec3::broadcast(index as f32));
+            ordered.push(index, tile);
+            if index == 2 {
+                assert_eq!(ordered.next(), 0);
+            }
+        }
+        assert_eq!(ordered.next(), 3);
+        assert_eq!(film.pixel(0, 0).weight(), 3.0);
+        assert_eq!(rgb(film.get(0, 0)), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn crop_keeps_pixels_within_bounds() {
+        let mut film = Film::new(3, 3);
+        film.add(1, 2, &math::Vec3::broadcast(1.0), 1.0);
+        let crop = film.crop(&Bounds::new(1, 1, 3, 3));
+        assert_eq!((crop.width(), crop.height()), (2, 2));
+        assert_eq!(rgb(crop.get(0, 1)), (1.0, 1.0, 1.0));
+    }
+
+    #[test]
+    fn crop_resolves_normalized_windows_outward() {
+        let crop = Crop::Normalized { x0: 0.25, y0: 0.0, x1: 0.6, y1: 2.0 };
+        assert_eq!(crop.resolve(10, 4), Bounds::new(2, 0, 6, 4));
+    }
+}
@@ -0,0 +1,245 @@
+use crate::math;
+
+/// Pixel reconstruction filter, centered at the origin and measured in pixels.
+pub trait Filter: std::fmt::Debug + Send + Sync {
+    /// Half-width of the filter's support
+    fn radius(&self) -> f32;
+
+    /// Weight of a sample at offset `(x, y)` from a pixel center
+    fn eval(&self, x: f32, y: f32) -> f32;
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Box {
+    radius: f32,
+}
+
+impl Box {
+    pub fn new(radius: f32) -> Self {
+        Box { radius }
+    }
+}
+
+impl Filter for Box {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        if x.abs() <= self.radius && y.abs() <= self.radius { 1.0 } else { 0.0 }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Tent {
+    radius: f32,
+}
+
+impl Tent {
+    pub fn new(radius: f32) -> Self {
+        Tent { radius }
+    }
+}
+
+impl Filter for Tent {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        math::max(0.0, self.radius - x.abs()) *
+        math::max(0.0, self.radius - y.abs())
+    }
+}
+
+/// Gaussian shifted down to reach zero at its radius.
+#[derive(Copy, Clone, Debug)]
+pub struct Gaussian {
+    radius: f32,
+    alpha: f32,
+    edge: f32,
+}
+
+impl Gaussian {
+    pub fn new(radius: f32, alpha: f32) -> Self {
+        Gaussian { radius, alpha, edge: (-alpha * radius * radius).exp() }
+    }
+
+    fn gaussian(&self, x: f32) -> f32 {
+        math::max(0.0, (-self.alpha * x * x).exp() - self.edge)
+    }
+}
+
+impl Filter for Gaussian {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.gaussian(x) * self.gaussian(y)
+    }
+}
+
+/// Cubic filter parameterized by `b` and `c`.
+///
+/// See: https://www.cs.utexas.edu/~fussell/courses/cs384g-fall2013/lectures/mitchell/Mitchell.pdf
+#[derive(Copy, Clone, Debug)]
+pub struct Mitchell {
+    radius: f32,
+    b: f32,
+    c: f32,
+}
+
+impl Mitchell {
+    pub fn new(radius: f32, b: f32, c: f32) -> Self {
+        Mitchell { radius, b, c }
+    }
+
+    fn mitchell(&self, x: f32) -> f32 {
+        let (b, c) = (self.b, self.c);
+        let x = (2.0 * x / self.radius).abs();
+        if x > 2.0 {
+            0.0
+        } else if x > 1.0 {
+            ((-b - 6.0 * c) * x * x * x
+                + (6.0 * b + 30.0 * c) * x * x
+                + (-12.0 * b - 48.0 * c) * x
+                + (8.0 * b + 24.0 * c)) / 6.0
+        } else {
+            ((12.0 - 9.0 * b - 6.0 * c) * x * x * x
+                + (-18.0 + 12.0 * b + 6.0 * c) * x * x
+                + (6.0 - 2.0 * b)) / 6.0
+        }
+    }
+}
+
+impl Filter for Mitchell {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.mitchell(x) * self.mitchell(y)
+    }
+}
+
+/// Sinc windowed by a wider sinc with `tau` lobes.
+#[derive(Copy, Clone, Debug)]
+pub struct Lanczos {
+    radius: f32,
+    tau: f32,
+}
+
+impl Lanczos {
+    pub fn new(radius: f32, tau: f32) -> Self {
+        Lanczos { radius, tau }
+    }
+
+    fn lanczos(&self, x: f32) -> f32 {
+        let x = x.abs();
+        if x > self.radius { 0.0 } else { sinc(x) * sinc(x / self.tau) }
+    }
+}
+
+impl Filter for Lanczos {
+    fn radius(&self) -> f32 {
+        self.radius
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        self.lanczos(x) * self.lanczos(y)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x.abs() < 1e-5 { return 1.0 }
+    let x = x * math::PI;
+    x.sin() / x
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Any {
+    Box(Box),
+    Tent(Tent),
+    Gaussian(Gaussian),
+    Mitchell(Mitchell),
+    Lanczos(Lanczos),
+}
+
+impl Default for Any {
+    /// Single-pixel box filter, which averages the samples within each pixel.
+    fn default() -> Self {
+        Any::Box(Box::new(0.5))
+    }
+}
+
+impl Filter for Any {
+    fn radius(&self) -> f32 {
+        match self {
+            Any::Box(filter) => filter.radius(),
+            Any::Tent(filter) => filter.radius(),
+            Any::Gaussian(filter) => filter.radius(),
+            Any::Mitchell(filter) => filter.radius(),
+            Any::Lanczos(filter) => filter.radius(),
+        }
+    }
+
+    fn eval(&self, x: f32, y: f32) -> f32 {
+        match self {
+            Any::Box(filter) => filter.eval(x, y),
+            Any::Tent(filter) => filter.eval(x, y),
+            Any::Gaussian(filter) => filter.eval(x, y),
+            Any::Mitchell(filter) => filter.eval(x, y),
+            Any::Lanczos(filter) => filter.eval(x, y),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Weights of `filter` at its center and at the edge of its support.
+    fn weights<F: Filter>(filter: F) -> (f32, f32) {
+        (filter.eval(0.0, 0.0), filter.eval(filter.radius(), 0.0))
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{} != {}", actual, expected);
+    }
+
+    #[test]
+    fn box_is_flat_to_its_edge() {
+        assert_eq!(weights(Box::new(0.5)), (1.0, 1.0));
+        assert_eq!(Box::new(0.5).eval(0.6, 0.0), 0.0);
+    }
+
+    #[test]
+    fn tent_falls_to_zero() {
+        assert_eq!(weights(Tent::new(2.0)), (4.0, 0.0));
+    }
+
+    #[test]
+    fn gaussian_falls_to_zero() {
+        let (center, edge) = weights(Gaussian::new(2.0, 0.5));
+        let tail = (-2.0f32).exp();
+        assert_close(center, (1.0 - tail) * (1.0 - tail));
+        assert_close(edge, 0.0);
+    }
+
+    #[test]
+    fn mitchell_falls_to_zero() {
+        let (b, c) = (1.0 / 3.0, 1.0 / 3.0);
+        let (center, edge) = weights(Mitchell::new(2.0, b, c));
+        let peak = (6.0 - 2.0 * b) / 6.0;
+        assert_close(center, peak * peak);
+        assert_close(edge, 0.0);
+    }
+
+    #[test]
+    fn lanczos_falls_to_zero() {
+        let (center, edge) = weights(Lanczos::new(3.0, 3.0));
+        assert_close(center, 1.0);
+        assert_close(edge, 0.0);
+    }
+}
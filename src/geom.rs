@@ -6,14 +6,20 @@ mod sphere;
 mod mesh;
 mod tri;
 mod translate;
+mod transform;
+mod motion;
+mod object;
 mod quad;
 mod sdf;
 
 pub use bound::Box3;
-pub use mesh::Mesh;
+pub use mesh::{Mesh, Prototype};
+pub use motion::{Key, Motion};
+pub use object::Object;
 pub use sdf::{SDF, Shape};
 pub use sphere::Sphere;
 pub use quad::Quad;
+pub use transform::Transform;
 pub use translate::Translate;
 pub use tri::Tri;
 
@@ -40,6 +46,9 @@ pub struct Hit<'scene> {
 
     /// Light emission at hit point
     pub emit: Option<math::Vec3>,
+
+    /// ID of the top-level surface hit, numbered in declaration order
+    pub object: usize,
 }
 
 /// Represents an object that can interact with light rays.
@@ -68,8 +77,10 @@ impl<'scene, T> Surface<'scene> for &T where T: Surface<'scene> + ?Sized {
 pub enum Any<'scene> {
     Box3(Box3),
     Mesh(Mesh<'scene>),
+    Motion(Motion<'scene>),
     Sphere(Sphere<'scene>),
     Quad(Quad<'scene>),
+    Transform(Transform<'scene>),
     Translate(Translate<'scene>),
     Tri(Tri<'scene>),
 }
@@ -79,8 +90,10 @@ impl<'scene> Surface<'scene> for Any<'scene> {
         match self {
             Any::Box3(surface) => surface.bound(),
             Any::Mesh(surface) => surface.bound(),
+            Any::Motion(surface) => surface.bound(),
             Any::Sphere(surface) => surface.bound(),
             Any::Quad(surface) => surface.bound(),
+            Any::Transform(surface) => surface.bound(),
             Any::Translate(surface) => surface.bound(),
             Any::Tri(surface) => surface.bound(),
         }
@@ -90,8 +103,10 @@ impl<'scene> Surface<'scene> for Any<'scene> {
         match self {
             Any::Box3(surface) => surface.hit(ray, hit),
             Any::Mesh(surface) => surface.hit(ray, hit),
+            Any::Motion(surface) => surface.hit(ray, hit),
             Any::Sphere(surface) => surface.hit(ray, hit),
             Any::Quad(surface) => surface.hit(ray, hit),
+            Any::Transform(surface) => surface.hit(ray, hit),
             Any::Translate(surface) => surface.hit(ray, hit),
             Any::Tri(surface) => surface.hit(ray, hit),
         }
@@ -101,8 +116,10 @@ impl<'scene> Surface<'scene> for Any<'scene> {
         match self {
             Any::Box3(surface) => surface.hit_any(ray),
             Any::Mesh(surface) => surface.hit_any(ray),
+            Any::Motion(surface) => surface.hit_any(ray),
             Any::Sphere(surface) => surface.hit_any(ray),
             Any::Quad(surface) => surface.hit_any(ray),
+            Any::Transform(surface) => surface.hit_any(ray),
             Any::Translate(surface) => surface.hit_any(ray),
             Any::Tri(surface) => surface.hit_any(ray),
         }
@@ -81,6 +81,15 @@ impl Box3 {
         Box3 { min, max }
     }
 
+    /// The eight corners of the box.
+    pub fn corners(&self) -> impl Iterator<Item = Vec3> + '_ {
+        (0..8).map(move |corner| Vec3::new(
+            self[corner & 1].x(),
+            self[(corner >> 1) & 1].y(),
+            self[(corner >> 2) & 1].z(),
+        ))
+    }
+
     pub fn offset(&self, v: &Vec3) -> Vec3 {
         let m = self.max.gt(&self.min);
         let p = v - self.min;
@@ -114,7 +123,7 @@ impl<'scene> geom::Surface<'scene> for Box3 {
 
     /// See: https://medium.com/@bromanz/another-view-on-the-classic-ray-aabb-intersection-algorithm-for-bvh-traversal-41125138b525
     fn hit_any(&self, ray: &Ray) -> bool {
-        if cfg!(feature = "stats") {
+        if crate::stats::enabled() {
             crate::stats::INTERSECTION_TESTS.inc();
             crate::stats::BOUNDING_BOX_INTERSECTION_TESTS.inc();
         }
@@ -4,30 +4,43 @@ use crate::bxdf;
 use crate::geom;
 use crate::math;
 
+/// Triangles and their BVH, loaded once and shared by every mesh
+/// that instances them.
+#[derive(Copy, Clone, Debug)]
+pub struct Prototype<'scene> {
+    internal: bvh::Tree<'scene, geom::Tri<'scene>>,
+}
+
+impl<'scene> Prototype<'scene> {
+    pub fn new(arena: &'scene arena::Arena, triangles: &[geom::Tri<'scene>]) -> Self {
+        let internal = bvh::Tree::new(arena, triangles);
+        Prototype { internal }
+    }
+}
+
+/// Instance of a prototype with its own material.
+///
+/// Place instances with `geom::Transform`: the scene BVH is then built over
+/// instances, and each instance's rays descend into the shared prototype BVH.
 #[derive(Copy, Clone, Debug)]
 pub struct Mesh<'scene> {
     bxdf: &'scene bxdf::Any<'scene>,
-    internal: bvh::Tree<'scene, geom::Tri<'scene>>,
+    prototype: Prototype<'scene>,
 }
 
 impl<'scene> Mesh<'scene> {
-    pub fn new(
-        arena: &'scene arena::Arena,
-        bxdf: &'scene bxdf::Any<'scene>,
-        triangles: &[geom::Tri<'scene>],
-    ) -> Self {
-        let internal = bvh::Tree::new(arena, triangles);
-        Mesh { bxdf, internal }
+    pub fn new(bxdf: &'scene bxdf::Any<'scene>, prototype: Prototype<'scene>) -> Self {
+        Mesh { bxdf, prototype }
     }
 }
 
 impl<'scene> geom::Surface<'scene> for Mesh<'scene> {
     fn bound(&self) -> geom::Box3 {
-        self.internal.bound()
+        self.prototype.internal.bound()
     }
 
     fn hit(&self, ray: &mut math::Ray, hit: &mut geom::Hit<'scene>) -> bool {
-        if self.internal.hit(ray, hit) {
+        if self.prototype.internal.hit(ray, hit) {
             hit.bxdf = Some(self.bxdf);
             hit.emit = None;
             true
@@ -37,6 +50,6 @@ impl<'scene> geom::Surface<'scene> for Mesh<'scene> {
     }
 
     fn hit_any(&self, ray: &math::Ray) -> bool {
-        self.internal.hit_any(ray)
+        self.prototype.internal.hit_any(ray)
     }
 }
@@ -0,0 +1,117 @@
+use crate::arena;
+use crate::geom;
+use crate::math::{Mat4, Quat, Ray, Vec3};
+
+/// Number of evenly spaced times between each pair of keys at which the
+/// swept bounds are sampled.
+const BOUND_SAMPLES: usize = 32;
+
+/// Placement of a surface at one key: scaled, then rotated, then translated.
+#[derive(Copy, Clone, Debug)]
+pub struct Key {
+    pub translate: Vec3,
+    pub rotate: Quat,
+    pub scale: Vec3,
+}
+
+impl Default for Key {
+    fn default() -> Self {
+        Key {
+            translate: Vec3::new(0.0, 0.0, 0.0),
+            rotate: Quat::identity(),
+            scale: Vec3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl Key {
+    /// Blend between `self` at `t = 0.0` and `other` at `t = 1.0`,
+    /// interpolating rotation spherically and everything else linearly.
+    pub fn lerp(&self, other: &Key, t: f32) -> Key {
+        Key {
+            translate: self.translate.lerp(&other.translate, t),
+            rotate: self.rotate.slerp(&other.rotate, t),
+            scale: self.scale.lerp(&other.scale, t),
+        }
+    }
+
+    /// Local to world matrix, with its inverse built from the parts
+    /// rather than by general matrix inversion.
+    fn matrices(&self) -> (Mat4, Mat4) {
+        let rotate = self.rotate.matrix();
+        let matrix = Mat4::translate(self.translate) * rotate * Mat4::scale(self.scale);
+        let inverse = Mat4::scale(self.scale.inv()) * rotate.transpose() * Mat4::translate(-self.translate);
+        (matrix, inverse)
+    }
+}
+
+/// Surface placed by keys in time, measured in frames, interpolated
+/// in between and held constant before the first and after the last.
+#[derive(Copy, Clone, Debug)]
+pub struct Motion<'scene> {
+    keys: &'scene [(f32, Key)],
+    surface: &'scene geom::Any<'scene>,
+}
+
+impl<'scene> Motion<'scene> {
+    pub fn new(arena: &'scene arena::Arena, mut keys: Vec<(f32, Key)>, surface: &'scene geom::Any<'scene>) -> Self {
+        assert!(!keys.is_empty(), "[INTERNAL ERROR]: motion requires at least one key");
+        keys.sort_by(|(a, _), (b, _)| a.total_cmp(b));
+        let keys = unsafe {
+            let arr = arena.alloc_slice_mut(keys.len());
+            arr.copy_from_slice(&keys);
+            arr
+        };
+        Motion { keys, surface }
+    }
+
+    fn transform(&self, time: f32) -> geom::Transform<'scene> {
+        let (matrix, inverse) = self.at(time).matrices();
+        geom::Transform::with_inverse(matrix, inverse, self.surface)
+    }
+
+    fn at(&self, time: f32) -> Key {
+        let next = self.keys.partition_point(|(key, _)| *key <= time);
+        match (next.checked_sub(1), self.keys.get(next)) {
+        | (Some(prev), Some((t1, k1))) => {
+            let (t0, k0) = &self.keys[prev];
+            k0.lerp(k1, (time - t0) / (t1 - t0))
+        }
+        | (Some(prev), None) => self.keys[prev].1,
+        | (None, _) => self.keys[0].1,
+        }
+    }
+}
+
+impl<'scene> geom::Surface<'scene> for Motion<'scene> {
+    /// Bounds the surface over its whole path by sampling its placement
+    /// between each pair of keys, since rotation sweeps it along arcs.
+    fn bound(&self) -> geom::Box3 {
+        let corners = self.surface.bound().corners().collect::<Vec<_>>();
+        let bound = |key: &Key| {
+            let (matrix, _) = key.matrices();
+            corners
+                .iter()
+                .map(|corner| matrix.point(corner))
+                .fold(geom::Box3::default(), |acc, corner| acc.union_v(&corner))
+        };
+
+        let mut swept = bound(&self.keys[0].1);
+        for pair in self.keys.windows(2) {
+            let ((_, k0), (_, k1)) = (&pair[0], &pair[1]);
+            for sample in 1..=BOUND_SAMPLES {
+                let t = sample as f32 / BOUND_SAMPLES as f32;
+                swept = swept.union_b(&bound(&k0.lerp(k1, t)));
+            }
+        }
+        swept
+    }
+
+    fn hit(&self, ray: &mut Ray, hit: &mut geom::Hit<'scene>) -> bool {
+        self.transform(ray.time).hit(ray, hit)
+    }
+
+    fn hit_any(&self, ray: &Ray) -> bool {
+        self.transform(ray.time).hit_any(ray)
+    }
+}
@@ -0,0 +1,34 @@
+use crate::math::Ray;
+use crate::geom;
+
+/// Top-level surface of a scene, which tags its hits with its ID.
+#[derive(Copy, Clone, Debug)]
+pub struct Object<'scene> {
+    id: usize,
+    surface: &'scene geom::Any<'scene>,
+}
+
+impl<'scene> Object<'scene> {
+    pub fn new(id: usize, surface: &'scene geom::Any<'scene>) -> Self {
+        Object { id, surface }
+    }
+}
+
+impl<'scene> geom::Surface<'scene> for Object<'scene> {
+    fn bound(&self) -> geom::Box3 {
+        self.surface.bound()
+    }
+
+    fn hit(&self, ray: &mut Ray, hit: &mut geom::Hit<'scene>) -> bool {
+        if self.surface.hit(ray, hit) {
+            hit.object = self.id;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn hit_any(&self, ray: &Ray) -> bool {
+        self.surface.hit_any(ray)
+    }
+}
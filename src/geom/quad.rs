@@ -37,34 +37,55 @@ impl<'scene> Quad<'scene> {
     pub fn as_light(&self) -> light::Any<'scene> {
         light::Any::Quad(*self)
     }
-}
-
-impl<'scene> geom::Surface<'scene> for Quad<'scene> {
 
-    fn bound(&self) -> geom::Box3 {
-        self.bound
+    pub fn area(&self) -> f32 {
+        self.u.cross(&self.v).len()
     }
 
-    fn hit(&self, ray: &mut math::Ray, hit: &mut geom::Hit<'scene>) -> bool {
+    /// Distance and texture coordinates where `ray` crosses the quad, if it
+    /// does so between `ray.min` and `ray.max`.
+    pub fn intersect(&self, ray: &math::Ray) -> Option<(f32, f32, f32)> {
 
         const EPSILON: f32 = 0.0000001;
 
         let h = ray.d.cross(&self.v);
         let det = self.u.dot(&h);
 
-        if det > -EPSILON && det < EPSILON { return false }
+        if det > -EPSILON && det < EPSILON { return None }
 
         let inv = 1.0 / det;
         let s = ray.p - self.p;
         let u = inv * s.dot(&h);
-        if u < 0.0 || u > 1.0 { return false }
+        if u < 0.0 || u > 1.0 { return None }
 
         let q = s.cross(&self.u);
         let v = inv * ray.d.dot(&q);
-        if v < 0.0 || v > 1.0 { return false }
+        if v < 0.0 || v > 1.0 { return None }
 
         let t = inv * self.v.dot(&q);
-        if t < ray.min || t > ray.max { return false }
+        if t < ray.min || t > ray.max { return None }
+
+        Some((t, u, v))
+    }
+}
+
+impl<'scene> geom::Surface<'scene> for Quad<'scene> {
+
+    fn bound(&self) -> geom::Box3 {
+        self.bound
+    }
+
+    fn hit(&self, ray: &mut math::Ray, hit: &mut geom::Hit<'scene>) -> bool {
+
+        if crate::stats::enabled() {
+            crate::stats::INTERSECTION_TESTS.inc();
+            crate::stats::QUAD_INTERSECTION_TESTS.inc();
+        }
+
+        let (t, u, v) = match self.intersect(ray) {
+        | Some(intersection) => intersection,
+        | None => return false,
+        };
 
         ray.set_max(t);
         hit.t = t;
@@ -80,25 +101,11 @@ impl<'scene> geom::Surface<'scene> for Quad<'scene> {
 
     fn hit_any(&self, ray: &math::Ray) -> bool {
 
-        const EPSILON: f32 = 0.0000001;
-
-        let h = ray.d.cross(&self.v);
-        let det = self.u.dot(&h);
-
-        if det > -EPSILON && det < EPSILON { return false }
-
-        let inv = 1.0 / det;
-        let s = ray.p - self.p;
-        let u = inv * s.dot(&h);
-        if u < 0.0 || u > 1.0 { return false }
-
-        let q = s.cross(&self.u);
-        let v = inv * ray.d.dot(&q);
-        if v < 0.0 || v > 1.0 { return false }
-
-        let t = inv * self.v.dot(&q);
-        if t < ray.min || t > ray.max { return false }
+        if crate::stats::enabled() {
+            crate::stats::INTERSECTION_TESTS.inc();
+            crate::stats::QUAD_INTERSECTION_TESTS.inc();
+        }
 
-        true
+        self.intersect(ray).is_some()
     }
 }
@@ -30,7 +30,7 @@ impl<'scene> geom::Surface<'scene> for Sphere<'scene> {
 
     fn hit(&self, ray: &mut math::Ray, hit: &mut geom::Hit<'scene>) -> bool {
 
-        if cfg!(feature = "stats") {
+        if crate::stats::enabled() {
             crate::stats::INTERSECTION_TESTS.inc();
             crate::stats::SPHERE_INTERSECTION_TESTS.inc();
         }
@@ -60,15 +60,15 @@ impl<'scene> geom::Surface<'scene> for Sphere<'scene> {
         hit.n = (hit.p - self.center) / self.radius;
         hit.bxdf = Some(self.bxdf);
         hit.emit = None;
-        let phi = hit.p.z().atan2(hit.p.x());
-        let theta = hit.p.y().asin();
+        let phi = hit.n.z().atan2(hit.n.x());
+        let theta = math::clamp(hit.n.y(), -1.0, 1.0).asin();
         hit.u = 1.0 - (phi + math::PI) / (2.0 * math::PI);
         hit.v = (theta + math::FRAC_PI_2) / math::PI;
         true
     }
 
     fn hit_any(&self, ray: &math::Ray) -> bool {
-        if cfg!(feature = "stats") {
+        if crate::stats::enabled() {
             crate::stats::INTERSECTION_TESTS.inc();
             crate::stats::SPHERE_INTERSECTION_TESTS.inc();
         }
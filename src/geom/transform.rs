@@ -0,0 +1,70 @@
+use crate::geom;
+use crate::math::{Mat4, Ray};
+
+/// Surface under an arbitrary invertible affine transformation.
+///
+/// Rays are carried into the surface's local space and hits back out, so
+/// the same surface can be placed any number of times without copying it.
+#[derive(Copy, Clone, Debug)]
+pub struct Transform<'scene> {
+    /// Local to world
+    matrix: Mat4,
+
+    /// World to local
+    inverse: Mat4,
+
+    surface: &'scene geom::Any<'scene>,
+}
+
+impl<'scene> Transform<'scene> {
+    pub fn new(matrix: Mat4, surface: &'scene geom::Any<'scene>) -> Self {
+        let inverse = matrix
+            .inverse()
+            .expect("[INTERNAL ERROR]: transform must be invertible");
+        Transform { matrix, inverse, surface }
+    }
+
+    /// Like `new`, for callers that already know the inverse of `matrix`.
+    pub(crate) fn with_inverse(matrix: Mat4, inverse: Mat4, surface: &'scene geom::Any<'scene>) -> Self {
+        Transform { matrix, inverse, surface }
+    }
+
+    /// Carries `ray` into local space, along with the factor that converts
+    /// distances along it from world to local units.
+    fn local(&self, ray: &Ray) -> (Ray, f32) {
+        let d = self.inverse.vector(&ray.d);
+        let scale = d.len();
+        let mut local = ray.spawn(self.inverse.point(&ray.p), d);
+        local.set_min(ray.min * scale);
+        local.set_max(ray.max * scale);
+        (local, scale)
+    }
+}
+
+impl<'scene> geom::Surface<'scene> for Transform<'scene> {
+    fn bound(&self) -> geom::Box3 {
+        self.surface
+            .bound()
+            .corners()
+            .map(|corner| self.matrix.point(&corner))
+            .fold(geom::Box3::default(), |acc, corner| acc.union_v(&corner))
+    }
+
+    fn hit(&self, ray: &mut Ray, hit: &mut geom::Hit<'scene>) -> bool {
+        let (mut local, scale) = self.local(ray);
+        if self.surface.hit(&mut local, hit) {
+            hit.t /= scale;
+            hit.p = self.matrix.point(&hit.p);
+            hit.n = self.inverse.normal(&hit.n).normalize();
+            ray.set_max(local.max / scale);
+            true
+        } else {
+            false
+        }
+    }
+
+    fn hit_any(&self, ray: &Ray) -> bool {
+        let (local, _) = self.local(ray);
+        self.surface.hit_any(&local)
+    }
+}
@@ -21,7 +21,7 @@ impl<'scene> geom::Surface<'scene> for Tri<'scene> {
 
     fn hit(&self, ray: &mut Ray, hit: &mut geom::Hit<'scene>) -> bool {
 
-        if cfg!(feature = "stats") {
+        if crate::stats::enabled() {
             crate::stats::INTERSECTION_TESTS.inc();
             crate::stats::TRI_INTERSECTION_TESTS.inc();
         }
@@ -50,6 +50,8 @@ impl<'scene> geom::Surface<'scene> for Tri<'scene> {
 
         ray.set_max(t);
         hit.t = t;
+        hit.u = u;
+        hit.v = v;
         hit.p =
             self.vertices[0] * w +
             self.vertices[1] * u +
@@ -64,7 +66,7 @@ impl<'scene> geom::Surface<'scene> for Tri<'scene> {
 
     fn hit_any(&self, ray: &Ray) -> bool {
 
-        if cfg!(feature = "stats") {
+        if crate::stats::enabled() {
             crate::stats::INTERSECTION_TESTS.inc();
             crate::stats::TRI_INTERSECTION_TESTS.inc();
         }
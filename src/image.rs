@@ -0,0 +1,288 @@
+use std::io;
+use std::path;
+
+use crate::film;
+use crate::math;
+use crate::tone;
+
+mod exr;
+mod hdr;
+mod pfm;
+mod png;
+
+/// Supported output file formats.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// 8-bit gamma-corrected PNG
+    PNG,
+
+    /// Uncompressed 32-bit float OpenEXR
+    EXR,
+
+    /// Radiance RGBE
+    HDR,
+
+    /// Portable float map
+    PFM,
+}
+
+impl Format {
+    /// Determine the output format from the extension of `path`.
+    pub fn from_path<P: AsRef<path::Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref()
+            .extension()?
+            .to_str()?
+            .to_ascii_lowercase();
+        match extension.as_str() {
+        | "png" => Some(Format::PNG),
+        | "exr" => Some(Format::EXR),
+        | "hdr" | "rgbe" => Some(Format::HDR),
+        | "pfm" => Some(Format::PFM),
+        | _ => None,
+        }
+    }
+
+    /// Whether this format stores linear radiance without clamping.
+    pub fn is_hdr(&self) -> bool {
+        *self != Format::PNG
+    }
+
+    /// Whether `read` can load images in this format back.
+    pub fn is_readable(&self) -> bool {
+        matches!(self, Format::PNG | Format::PFM)
+    }
+}
+
+/// Write `pixels`, a row-major buffer of linear radiance with the top row first,
+/// to `path` in the format implied by its extension.
+///
+/// Only low dynamic range formats are passed through `tone`.
+pub fn write<P: AsRef<path::Path>>(
+    path: P,
+    width: usize,
+    height: usize,
+    pixels: &[math::Vec3],
+    tone: &tone::Tone,
+) -> io::Result<()> {
+    let path = path.as_ref();
+    if width == 0 || height == 0 || pixels.len() != width * height {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("cannot write {} pixels as a {}x{} image", pixels.len(), width, height),
+        ))
+    }
+    match Format::from_path(path) {
+    | Some(Format::PNG) => png::write(path, width, height, pixels, tone),
+    | Some(Format::EXR) => exr::write(path, width, height, pixels),
+    | Some(Format::HDR) => hdr::write(path, width, height, pixels),
+    | Some(Format::PFM) => pfm::write(path, width, height, pixels),
+    | None => Err(io::Error::new(
+        io::ErrorKind::InvalidInput,
+        format!("unsupported image format: '{}'", path.display()),
+    )),
+    }
+}
+
+/// Read an image previously written by `write`, returning its width, height and pixels.
+///
+/// High dynamic range formats yield linear radiance, while PNG yields display-encoded
+/// values in `[0, 1]`. Only PNG and PFM can be read back.
+pub fn read<P: AsRef<path::Path>>(path: P) -> io::Result<(usize, usize, Vec<math::Vec3>)> {
+    let path = path.as_ref();
+    match Format::from_path(path) {
+    | Some(Format::PNG) => png::read(path),
+    | Some(Format::PFM) => pfm::read(path),
+    | _ => Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!("cannot read image format: '{}'", path.display()),
+    )),
+    }
+}
+
+/// Film sink that writes an image file, choosing the format from its extension.
+#[derive(Clone, Debug)]
+pub struct File {
+    path: path::PathBuf,
+    tone: tone::Tone,
+}
+
+impl File {
+    pub fn new<P: Into<path::PathBuf>>(path: P, tone: tone::Tone) -> Self {
+        File { path: path.into(), tone }
+    }
+}
+
+impl film::Sink for File {
+    fn write(&self, film: &film::Film) -> io::Result<()> {
+        write(&self.path, film.width(), film.height(), &film.resolve(), &self.tone)
+    }
+}
+
+/// Film sink that pastes a window of the film into an existing image of the same size,
+/// then writes the result in the same format.
+#[derive(Clone, Debug)]
+pub struct Merge {
+    base: path::PathBuf,
+    path: path::PathBuf,
+    tone: tone::Tone,
+    bounds: film::Bounds,
+}
+
+impl Merge {
+    pub fn new<B, P>(base: B, path: P, tone: tone::Tone, bounds: film::Bounds) -> Self
+    where B: Into<path::PathBuf>,
+          P: Into<path::PathBuf>,
+    {
+        Merge { base: base.into(), path: path.into(), tone, bounds }
+    }
+}
+
+impl film::Sink for Merge {
+    fn write(&self, film: &film::Film) -> io::Result<()> {
+        let format = Format::from_path(&self.path);
+        if Format::from_path(&self.base) != format {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("cannot merge '{}' into a different format", self.base.display()),
+            ))
+        }
+
+        let (width, height, mut pixels) = read(&self.base)?;
+        if width != film.width() || height != film.height() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!(
+                    "'{}' is {}x{}, but the render is {}x{}",
+                    self.base.display(), width, height, film.width(), film.height(),
+                ),
+            ))
+        }
+
+        // PNG pixels were read back display-encoded, so encode the new ones to match
+        let tone = match format {
+        | Some(Format::PNG) => Some(self.tone),
+        | _ => None,
+        };
+
+        for y in self.bounds.y0..self.bounds.y1 {
+            for x in self.bounds.x0..self.bounds.x1 {
+                let c = film.get(x, y);
+                pixels[y * width + x] = tone.map_or(c, |tone| tone.apply(&c));
+            }
+        }
+
+        write(&self.path, width, height, &pixels, &tone::Tone::identity())
+    }
+}
+
+/// Film sink that writes the number of samples taken in each pixel as a
+/// false color image, from blue (none) to red (`budget` or more).
+#[derive(Clone, Debug)]
+pub struct Heatmap {
+    path: path::PathBuf,
+    budget: usize,
+}
+
+impl Heatmap {
+    pub fn new<P: Into<path::PathBuf>>(path: P, budget: usize) -> Self {
+        Heatmap { path: path.into(), budget }
+    }
+}
+
+impl film::Sink for Heatmap {
+    fn write(&self, film: &film::Film) -> io::Result<()> {
+        let budget = self.budget.max(1) as f32;
+        let pixels = film.pixels()
+            .iter()
+            .map(|pixel| heat(pixel.samples() as f32 / budget))
+            .collect::<Vec<_>>();
+        write(&self.path, film.width(), film.height(), &pixels, &tone::Tone::identity())
+    }
+}
+
+/// Map `t` in `[0, 1]` onto a blue, cyan, green, yellow, red color ramp.
+pub fn heat(t: f32) -> math::Vec3 {
+    let t = math::clamp(t, 0.0, 1.0) * 4.0;
+    let ramp = |center: f32| math::clamp(1.5 - (t - center).abs(), 0.0, 1.0);
+    math::Vec3::new(ramp(3.0), ramp(2.0), ramp(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::fs;
+
+    /// Fresh path in the system temporary directory with extension `extension`.
+    fn temp(name: &str, extension: &str) -> path::PathBuf {
+        std::env::temp_dir().join(format!("photon-image-{}-{}.{}", std::process::id(), name, extension))
+    }
+
+    /// 3x2 image with distinct, exactly representable pixels.
+    fn pixels() -> Vec<math::Vec3> {
+        (0..6)
+            .map(|i| i as f32 / 8.0)
+            .map(|i| math::Vec3::new(i, 0.5 - i / 2.0, 4.0 * i))
+            .collect()
+    }
+
+    /// Write `pixels` to a temporary file and return its contents.
+    fn written(name: &str, extension: &str) -> Vec<u8> {
+        let path = temp(name, extension);
+        write(&path, 3, 2, &pixels(), &tone::Tone::identity()).unwrap();
+        let bytes = fs::read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        bytes
+    }
+
+    #[test]
+    fn pfm_round_trip() {
+        let path = temp("round-trip", "pfm");
+        write(&path, 3, 2, &pixels(), &tone::Tone::identity()).unwrap();
+        let (width, height, read) = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (3, 2));
+        for (lhs, rhs) in read.iter().zip(&pixels()) {
+            assert_eq!((lhs.r(), lhs.g(), lhs.b()), (rhs.r(), rhs.g(), rhs.b()));
+        }
+    }
+
+    #[test]
+    fn png_round_trip() {
+        let path = temp("round-trip", "png");
+        write(&path, 3, 2, &pixels(), &tone::Tone::identity()).unwrap();
+        let (width, height, read) = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!((width, height), (3, 2));
+        for (lhs, rhs) in read.iter().zip(&pixels()) {
+            let rhs = rhs.min(&math::Vec3::new(1.0, 1.0, 1.0));
+            for (l, r) in [(lhs.r(), rhs.r()), (lhs.g(), rhs.g()), (lhs.b(), rhs.b())] {
+                assert!((l - r).abs() < 1.0 / 255.0, "{:?} != {:?}", lhs, rhs);
+            }
+        }
+    }
+
+    #[test]
+    fn exr_header() {
+        let bytes = written("header", "exr");
+        assert_eq!(bytes[0..4], 20000630u32.to_le_bytes());
+        assert_eq!(bytes[4..8], 2u32.to_le_bytes());
+        assert_eq!(&bytes[8..17], b"channels\0");
+    }
+
+    #[test]
+    fn hdr_header() {
+        let bytes = written("header", "hdr");
+        let header = b"#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y 2 +X 3\n";
+        assert_eq!(&bytes[..header.len()], header);
+        assert_eq!(bytes.len(), header.len() + 4 * 6);
+    }
+
+    #[test]
+    fn rejects_empty_image() {
+        let path = temp("empty", "exr");
+        let error = write(&path, 0, 2, &[], &tone::Tone::identity()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidInput);
+        assert!(!path.exists());
+    }
+}
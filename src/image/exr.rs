@@ -0,0 +1,90 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path;
+
+use byteorder::WriteBytesExt;
+use byteorder::LE;
+
+use crate::math;
+
+const MAGIC: u32 = 20000630;
+const VERSION: u32 = 2;
+
+/// Pixel type tag for 32-bit floats
+const FLOAT: i32 = 2;
+
+/// Channels must be listed in alphabetical order
+const CHANNELS: [&str; 3] = ["B", "G", "R"];
+
+/// Write a single-part, uncompressed scanline image with 32-bit float channels.
+///
+/// See: https://www.openexr.com/documentation/openexrfilelayout.pdf
+pub fn write(
+    path: &path::Path,
+    width: usize,
+    height: usize,
+    pixels: &[math::Vec3],
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+    let mut header = Vec::new();
+
+    header.write_u32::<LE>(MAGIC)?;
+    header.write_u32::<LE>(VERSION)?;
+
+    let mut channels = Vec::new();
+    for channel in &CHANNELS {
+        channels.write_all(channel.as_bytes())?;
+        channels.write_u8(0)?;
+        channels.write_i32::<LE>(FLOAT)?;
+        channels.write_u8(0)?;          // pLinear
+        channels.write_all(&[0; 3])?;   // reserved
+        channels.write_i32::<LE>(1)?;   // xSampling
+        channels.write_i32::<LE>(1)?;   // ySampling
+    }
+    channels.write_u8(0)?;
+
+    let mut window = Vec::new();
+    window.write_i32::<LE>(0)?;
+    window.write_i32::<LE>(0)?;
+    window.write_i32::<LE>(width as i32 - 1)?;
+    window.write_i32::<LE>(height as i32 - 1)?;
+
+    attribute(&mut header, "channels", "chlist", &channels)?;
+    attribute(&mut header, "compression", "compression", &[0])?;
+    attribute(&mut header, "dataWindow", "box2i", &window)?;
+    attribute(&mut header, "displayWindow", "box2i", &window)?;
+    attribute(&mut header, "lineOrder", "lineOrder", &[0])?;
+    attribute(&mut header, "pixelAspectRatio", "float", &1f32.to_le_bytes())?;
+    attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8])?;
+    attribute(&mut header, "screenWindowWidth", "float", &1f32.to_le_bytes())?;
+    header.write_u8(0)?;
+
+    out.write_all(&header)?;
+
+    // Offset table points at the start of each scanline block
+    let line = 4 * CHANNELS.len() * width;
+    let start = header.len() + 8 * height;
+    for y in 0..height {
+        out.write_u64::<LE>((start + y * (8 + line)) as u64)?;
+    }
+
+    for (y, row) in pixels.chunks(width).enumerate() {
+        out.write_i32::<LE>(y as i32)?;
+        out.write_i32::<LE>(line as i32)?;
+        for pixel in row { out.write_f32::<LE>(pixel.b())?; }
+        for pixel in row { out.write_f32::<LE>(pixel.g())?; }
+        for pixel in row { out.write_f32::<LE>(pixel.r())?; }
+    }
+
+    out.flush()
+}
+
+fn attribute<W: Write>(out: &mut W, name: &str, kind: &str, value: &[u8]) -> io::Result<()> {
+    out.write_all(name.as_bytes())?;
+    out.write_u8(0)?;
+    out.write_all(kind.as_bytes())?;
+    out.write_u8(0)?;
+    out.write_i32::<LE>(value.len() as i32)?;
+    out.write_all(value)
+}
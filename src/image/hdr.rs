@@ -0,0 +1,47 @@
+use std::fs;
+use std::io;
+use std::io::Write;
+use std::path;
+
+use crate::math;
+
+/// See: https://www.graphics.cornell.edu/~bjw/rgbe.html
+pub fn write(
+    path: &path::Path,
+    width: usize,
+    height: usize,
+    pixels: &[math::Vec3],
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+
+    write!(out, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", height, width)?;
+
+    // Scanlines are written flat, which readers accept in place of run-length encoding
+    for pixel in pixels {
+        out.write_all(&rgbe(pixel))?;
+    }
+
+    out.flush()
+}
+
+/// Encode a color as three 8-bit mantissas sharing an 8-bit exponent.
+fn rgbe(pixel: &math::Vec3) -> [u8; 4] {
+    let pixel = pixel.max(&math::Vec3::default());
+    let max = pixel.max_horizontal();
+
+    if !max.is_finite() || max < 1e-32 {
+        return [0; 4]
+    }
+
+    // Decompose `max` into `m * 2^e` with `m` in `[0.5, 1.0)`
+    let e = max.log2().floor() as i32 + 1;
+    let m = max / 2f32.powi(e);
+    let scale = m * 256.0 / max;
+
+    [
+        (pixel.r() * scale) as u8,
+        (pixel.g() * scale) as u8,
+        (pixel.b() * scale) as u8,
+        (e + 128) as u8,
+    ]
+}
@@ -0,0 +1,73 @@
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::io::Write;
+use std::path;
+
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use byteorder::BE;
+use byteorder::LE;
+
+use crate::math;
+
+/// See: http://www.pauldebevec.com/Research/HDR/PFM/
+pub fn write(
+    path: &path::Path,
+    width: usize,
+    height: usize,
+    pixels: &[math::Vec3],
+) -> io::Result<()> {
+    let mut out = io::BufWriter::new(fs::File::create(path)?);
+
+    // Negative scale indicates little-endian data
+    write!(out, "PF\n{} {}\n-1.0\n", width, height)?;
+
+    // Scanlines are stored from bottom to top
+    for row in pixels.chunks(width).rev() {
+        for pixel in row {
+            out.write_f32::<LE>(pixel.r())?;
+            out.write_f32::<LE>(pixel.g())?;
+            out.write_f32::<LE>(pixel.b())?;
+        }
+    }
+
+    out.flush()
+}
+
+pub fn read(path: &path::Path) -> io::Result<(usize, usize, Vec<math::Vec3>)> {
+    let mut input = io::BufReader::new(fs::File::open(path)?);
+    let invalid = |message: &str| io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}: {}", path.display(), message),
+    );
+
+    // Header is three lines: magic, dimensions and scale
+    let mut header = Vec::new();
+    for _ in 0..3 {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Err(invalid("truncated header"))
+        }
+        header.extend(line.split_whitespace().map(str::to_owned));
+    }
+    if header.len() != 4 || header[0] != "PF" {
+        return Err(invalid("expected a color PFM header"))
+    }
+    let width = header[1].parse::<usize>().map_err(|_| invalid("invalid width"))?;
+    let height = header[2].parse::<usize>().map_err(|_| invalid("invalid height"))?;
+    let scale = header[3].parse::<f32>().map_err(|_| invalid("invalid scale"))?;
+
+    let mut rows = vec![Vec::with_capacity(width); height];
+    for row in rows.iter_mut().rev() {
+        for _ in 0..width {
+            let mut read = || if scale < 0.0 {
+                input.read_f32::<LE>()
+            } else {
+                input.read_f32::<BE>()
+            };
+            row.push(math::Vec3::new(read()?, read()?, read()?));
+        }
+    }
+    Ok((width, height, rows.concat()))
+}
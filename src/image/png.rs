@@ -0,0 +1,36 @@
+use std::io;
+use std::path;
+
+use crate::math;
+use crate::tone;
+
+pub fn write(
+    path: &path::Path,
+    width: usize,
+    height: usize,
+    pixels: &[math::Vec3],
+    tone: &tone::Tone,
+) -> io::Result<()> {
+    let mut buffer = Vec::with_capacity(width * height * 3);
+    for pixel in pixels {
+        let c = tone.apply(pixel);
+        buffer.push((math::clamp(c.r(), 0.0, 1.0) * 255.99) as u8);
+        buffer.push((math::clamp(c.g(), 0.0, 1.0) * 255.99) as u8);
+        buffer.push((math::clamp(c.b(), 0.0, 1.0) * 255.99) as u8);
+    }
+    lodepng::encode24_file(path, &buffer, width, height)
+        .map_err(io::Error::other)
+}
+
+pub fn read(path: &path::Path) -> io::Result<(usize, usize, Vec<math::Vec3>)> {
+    let image = lodepng::decode24_file(path).map_err(io::Error::other)?;
+    let pixels = image.buffer
+        .iter()
+        .map(|pixel| math::Vec3::new(
+            pixel.r as f32 / 255.0,
+            pixel.g as f32 / 255.0,
+            pixel.b as f32 / 255.0,
+        ))
+        .collect();
+    Ok((image.width, image.height, pixels))
+}
@@ -1,6 +1,10 @@
 use std::fmt;
+use std::sync;
 
+use crate::camera;
+use crate::film;
 use crate::math;
+use crate::sample;
 use crate::scene;
 use crate::geom;
 use crate::geom::Surface;
@@ -10,29 +14,124 @@ mod point;
 mod path;
 mod bxdf;
 mod light;
+mod bdpt;
+mod photon;
+mod ao;
+mod distance;
+mod uv;
+mod albedo;
+mod material;
+mod object;
+mod heatmap;
 
 pub use normal::Normal;
 pub use point::Point;
 pub use path::Path;
 pub use bxdf::BxDF;
 pub use light::Light;
+pub use bdpt::BDPT;
+pub use photon::{Photon, PhotonCache, PhotonMap};
+pub use ao::AO;
+pub use distance::Distance;
+pub use uv::UV;
+pub use albedo::Albedo;
+pub use material::Material;
+pub use object::Object;
+pub use heatmap::{Heatmap, Work};
 
 pub trait Integrator<'scene>: Send + Sync + fmt::Debug {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, depth: usize) -> math::Vec3;
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, depth: usize) -> math::Vec3;
+
+    /// Radiance arriving along `ray`, cast as described by `view`, or black if it escapes the scene.
+    ///
+    /// Integrators that also trace paths from the lights push whatever those
+    /// carry straight to the camera onto `splats`, in any pixel of the image.
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        _: &View,
+        ray: &mut math::Ray,
+        hit: &mut geom::Hit<'scene>,
+        sampler: &mut S,
+        _: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+        if scene.hit(ray, hit) {
+            self.shade(scene, ray, hit, sampler, 0)
+        } else {
+            math::Vec3::default()
+        }
+    }
+
+    /// Photon map that every camera ray of sample `index` in `frame` gathers from,
+    /// for integrators that trace one before rendering.
+    fn photons(&self, _: &scene::Scene<'scene>, _: usize, _: usize) -> Option<sync::Arc<PhotonMap>> {
+        None
+    }
+
+    /// Whether each sample needs a pass of its own, because
+    /// every sample gathers from a different photon map.
+    fn per_sample(&self) -> bool {
+        false
+    }
 }
 
 impl<'a, 'scene, I> Integrator<'scene> for &'a I where I: Integrator<'scene> + ?Sized {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, depth: usize) -> math::Vec3 {
-        (*self).shade(scene, ray, hit, depth)
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, depth: usize) -> math::Vec3 {
+        (*self).shade(scene, ray, hit, sampler, depth)
+    }
+
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        view: &View,
+        ray: &mut math::Ray,
+        hit: &mut geom::Hit<'scene>,
+        sampler: &mut S,
+        splats: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+        (*self).trace(scene, view, ray, hit, sampler, splats)
+    }
+
+    fn photons(&self, scene: &scene::Scene<'scene>, frame: usize, index: usize) -> Option<sync::Arc<PhotonMap>> {
+        (*self).photons(scene, frame, index)
+    }
+
+    fn per_sample(&self) -> bool {
+        (*self).per_sample()
     }
 }
 
-pub fn shadowed<'scene>(scene: &scene::Scene<'scene>, p: &math::Vec3, d: &math::Vec3, t: f32) -> bool {
-    let mut shadow = math::Ray::new(*p, *d);
-    shadow.set_max(t);
+/// What a camera ray is traced for, besides the scene.
+pub struct View<'a> {
+    /// Camera the ray was cast from
+    pub camera: &'a camera::Camera,
+
+    /// Photons to gather, for integrators that trace them
+    pub photons: Option<&'a PhotonMap>,
+}
+
+pub fn shadowed<'scene>(scene: &scene::Scene<'scene>, shadow: &math::Ray, t: f32) -> bool {
+    let mut shadow = *shadow;
+
+    // Stop just short of the light, so area lights don't shadow themselves
+    shadow.set_max(t - shadow.min);
     scene.hit_any(&shadow)
 }
 
+/// Color for an integer ID, distinct for nearby IDs and the same from run to run.
+pub fn false_color(id: usize) -> math::Vec3 {
+    let hash = sample::hash(&[id as u64]);
+    let channel = |shift: u64| ((hash >> shift) & 0xff) as f32 / 255.0;
+    math::Vec3::new(channel(0), channel(8), channel(16))
+}
+
+/// Multiple importance sampling weight for a sample drawn with density `f`
+/// when another strategy could have drawn it with density `g`.
+pub fn power_heuristic(f: f32, g: f32) -> f32 {
+    let (f, g) = (f * f, g * g);
+    if f + g > 0.0 { f / (f + g) } else { 0.0 }
+}
+
 #[derive(Copy, Clone, Debug)]
 pub enum Any {
     Normal(Normal),
@@ -40,16 +139,99 @@ pub enum Any {
     Path(Path),
     BxDF(BxDF),
     Light(Light),
+    BDPT(BDPT),
+    Photon(Photon),
+    AO(AO),
+    Distance(Distance),
+    UV(UV),
+    Albedo(Albedo),
+    Material(Material),
+    Object(Object),
+    Heatmap(Heatmap),
 }
 
 impl<'scene> Integrator<'scene> for Any {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, depth: usize) -> math::Vec3 {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, depth: usize) -> math::Vec3 {
+        match self {
+            Any::Normal(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Point(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Path(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::BxDF(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Light(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::BDPT(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Photon(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::AO(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Distance(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::UV(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Albedo(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Material(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Object(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+            Any::Heatmap(integrator) => integrator.shade(scene, ray, hit, sampler, depth),
+        }
+    }
+
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        view: &View,
+        ray: &mut math::Ray,
+        hit: &mut geom::Hit<'scene>,
+        sampler: &mut S,
+        splats: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+        match self {
+            Any::Normal(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Point(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Path(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::BxDF(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Light(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::BDPT(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Photon(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::AO(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Distance(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::UV(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Albedo(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Material(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Object(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+            Any::Heatmap(integrator) => integrator.trace(scene, view, ray, hit, sampler, splats),
+        }
+    }
+
+    fn photons(&self, scene: &scene::Scene<'scene>, frame: usize, index: usize) -> Option<sync::Arc<PhotonMap>> {
+        match self {
+            Any::Normal(integrator) => integrator.photons(scene, frame, index),
+            Any::Point(integrator) => integrator.photons(scene, frame, index),
+            Any::Path(integrator) => integrator.photons(scene, frame, index),
+            Any::BxDF(integrator) => integrator.photons(scene, frame, index),
+            Any::Light(integrator) => integrator.photons(scene, frame, index),
+            Any::BDPT(integrator) => integrator.photons(scene, frame, index),
+            Any::Photon(integrator) => integrator.photons(scene, frame, index),
+            Any::AO(integrator) => integrator.photons(scene, frame, index),
+            Any::Distance(integrator) => integrator.photons(scene, frame, index),
+            Any::UV(integrator) => integrator.photons(scene, frame, index),
+            Any::Albedo(integrator) => integrator.photons(scene, frame, index),
+            Any::Material(integrator) => integrator.photons(scene, frame, index),
+            Any::Object(integrator) => integrator.photons(scene, frame, index),
+            Any::Heatmap(integrator) => integrator.photons(scene, frame, index),
+        }
+    }
+
+    fn per_sample(&self) -> bool {
         match self {
-            Any::Normal(integrator) => integrator.shade(scene, ray, hit, depth),
-            Any::Point(integrator) => integrator.shade(scene, ray, hit, depth),
-            Any::Path(integrator) => integrator.shade(scene, ray, hit, depth),
-            Any::BxDF(integrator) => integrator.shade(scene, ray, hit, depth),
-            Any::Light(integrator) => integrator.shade(scene, ray, hit, depth),
+            Any::Normal(integrator) => integrator.per_sample(),
+            Any::Point(integrator) => integrator.per_sample(),
+            Any::Path(integrator) => integrator.per_sample(),
+            Any::BxDF(integrator) => integrator.per_sample(),
+            Any::Light(integrator) => integrator.per_sample(),
+            Any::BDPT(integrator) => integrator.per_sample(),
+            Any::Photon(integrator) => integrator.per_sample(),
+            Any::AO(integrator) => integrator.per_sample(),
+            Any::Distance(integrator) => integrator.per_sample(),
+            Any::UV(integrator) => integrator.per_sample(),
+            Any::Albedo(integrator) => integrator.per_sample(),
+            Any::Material(integrator) => integrator.per_sample(),
+            Any::Object(integrator) => integrator.per_sample(),
+            Any::Heatmap(integrator) => integrator.per_sample(),
         }
     }
 }
@@ -0,0 +1,23 @@
+use crate::bxdf::BxDF;
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+/// Fraction of light the first hit's BxDF scatters back toward the camera.
+///
+/// Each sample scatters one direction from the BxDF, so that pixels
+/// average to its reflectance however it is made up.
+#[derive(Copy, Clone, Debug)]
+pub struct Albedo;
+
+impl<'scene> integrator::Integrator<'scene> for Albedo {
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, _: usize) -> math::Vec3 {
+        let bs = hit.bxdf.unwrap().sample(&-ray.d, &hit.n, sampler);
+        if bs.p < math::EPSILON {
+            return math::Vec3::default()
+        }
+        bs.v * bs.d.dot(&hit.n).abs() / bs.p
+    }
+}
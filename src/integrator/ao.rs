@@ -0,0 +1,48 @@
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+use crate::geom::Surface;
+
+/// Ambient occlusion, for previewing geometry without setting up lights.
+///
+/// Shades each camera hit with the fraction of cosine-weighted hemisphere
+/// rays that travel `distance` without hitting anything.
+#[derive(Copy, Clone, Debug)]
+pub struct AO {
+    /// Occlusion rays per camera hit
+    rays: usize,
+
+    /// Distance beyond which surfaces no longer occlude
+    distance: f32,
+}
+
+impl AO {
+    pub fn new(rays: usize, distance: f32) -> Self {
+        AO { rays, distance }
+    }
+}
+
+impl<'scene> integrator::Integrator<'scene> for AO {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, _: usize) -> math::Vec3 {
+        if self.rays == 0 { return math::Vec3::default() }
+
+        // Meshes can be seen from behind, so use whichever side faces the camera
+        let n = if hit.n.dot(&ray.d) > 0.0 { -hit.n } else { hit.n };
+        let (u, v) = math::basis(&n);
+
+        let mut open = 0;
+        for _ in 0..self.rays {
+            let d = math::cosine_sphere(sampler);
+            let mut occlusion = ray.spawn(hit.p, n * d.z() + u * d.x() + v * d.y());
+            occlusion.set_max(self.distance);
+            if !scene.hit_any(&occlusion) {
+                open += 1;
+            }
+        }
+
+        math::Vec3::broadcast(open as f32 / self.rays as f32)
+    }
+}
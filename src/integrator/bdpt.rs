@@ -0,0 +1,441 @@
+use crate::bxdf;
+use crate::bxdf::BxDF;
+use crate::camera;
+use crate::film;
+use crate::geom;
+use crate::integrator;
+use crate::light;
+use crate::light::Light;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+use crate::geom::Surface;
+
+/// Bidirectional path tracer.
+///
+/// Every sample traces one subpath from the camera and one from a light, then
+/// joins each prefix of the first to each prefix of the second. Any full path
+/// can be built several ways, so each is weighted with the power heuristic
+/// against all the others. Light subpaths joined straight to the lens can
+/// reach any pixel, so they are splatted onto the film rather than returned.
+#[derive(Copy, Clone, Debug)]
+pub struct BDPT {
+    /// Maximum recursion depth, counted the same way as for `Path`
+    depth: usize,
+}
+
+impl BDPT {
+    pub fn new(depth: usize) -> Self {
+        BDPT { depth }
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+enum Kind<'scene> {
+    Camera,
+
+    /// Light the subpath starts from, and whether it is a point
+    Light(&'scene light::Any<'scene>, bool),
+
+    /// Scattering surface, with its radiance and light if it emits toward the previous vertex
+    Surface(&'scene bxdf::Any<'scene>, Option<(math::Vec3, &'scene light::Any<'scene>)>),
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Vertex<'scene> {
+    kind: Kind<'scene>,
+
+    /// Position
+    p: math::Vec3,
+
+    /// Surface normal, or zero away from surfaces
+    n: math::Vec3,
+
+    /// Direction back toward the previous vertex
+    wo: math::Vec3,
+
+    /// Throughput from the start of the subpath up to here
+    beta: math::Vec3,
+
+    /// Whether the subpath left this vertex through a delta lobe
+    delta: bool,
+
+    /// Area density of sampling this vertex from the previous one
+    fwd: f32,
+
+    /// Area density of sampling this vertex from the next one, were the path traced the other way
+    rev: f32,
+}
+
+impl<'scene> Vertex<'scene> {
+    fn new(kind: Kind<'scene>, p: math::Vec3, n: math::Vec3, beta: math::Vec3) -> Self {
+        Vertex {
+            kind,
+            p,
+            n,
+            wo: math::Vec3::default(),
+            beta,
+            delta: false,
+            fwd: 0.0,
+            rev: 0.0,
+        }
+    }
+
+    fn surface(scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, beta: math::Vec3) -> Self {
+        let emitter = hit.emit.and_then(|emit| {
+            scene.lights()
+                .iter()
+                .copied()
+                .find(|light| light.pdf(ray) > 0.0)
+                .map(|light| (emit, light))
+        });
+        Vertex {
+            wo: -ray.d,
+            ..Vertex::new(Kind::Surface(hit.bxdf.unwrap(), emitter), hit.p, hit.n, beta)
+        }
+    }
+
+    /// Light that this vertex sends radiance from, if any.
+    fn emitter(&self) -> Option<&'scene light::Any<'scene>> {
+        match self.kind {
+        | Kind::Light(light, _) => Some(light),
+        | Kind::Surface(_, Some((_, light))) => Some(light),
+        | _ => None,
+        }
+    }
+
+    fn is_delta_light(&self) -> bool {
+        matches!(self.kind, Kind::Light(_, true))
+    }
+
+    /// BxDF value for light scattering between the previous vertex and `next`.
+    fn f(&self, next: &Vertex) -> math::Vec3 {
+        match self.kind {
+        | Kind::Surface(bxdf, _) => bxdf.eval(&self.wo, &(next.p - self.p).normalize(), &self.n),
+        | _ => math::Vec3::default(),
+        }
+    }
+
+    /// Convert solid angle density `pdf` of leaving toward `next` into area density at `next`.
+    fn convert(&self, pdf: f32, next: &Vertex) -> f32 {
+        let w = next.p - self.p;
+        let d2 = w.len_sq();
+        if d2 == 0.0 { return 0.0 }
+        let cos = if next.n.is_zero() { 1.0 } else { next.n.dot(&w).abs() / d2.sqrt() };
+        pdf * cos / d2
+    }
+
+    /// Area density of sampling `next` from this vertex, having arrived from `prev`.
+    fn pdf(&self, camera: &camera::Camera, prev: Option<&Vertex>, next: &Vertex) -> f32 {
+        let d = (next.p - self.p).normalize();
+        let pdf = match self.kind {
+        | Kind::Camera => camera.pdf(&self.p, &d),
+        | Kind::Light(..) => return self.pdf_light(next),
+        | Kind::Surface(bxdf, _) => {
+            let prev = prev.expect("[INTERNAL ERROR]: surface vertex without a predecessor");
+            bxdf.pdf(&(prev.p - self.p).normalize(), &d, &self.n)
+        }
+        };
+        self.convert(pdf, next)
+    }
+
+    /// Area density of this emitter sending light toward `next`.
+    fn pdf_light(&self, next: &Vertex) -> f32 {
+        match self.emitter() {
+        | Some(light) => {
+            let (_, dir) = light.pdf_emit(&(next.p - self.p).normalize());
+            self.convert(dir, next)
+        }
+        | None => 0.0,
+        }
+    }
+
+    /// Area density of a light subpath starting here, on its way toward `next`.
+    fn pdf_origin(&self, scene: &scene::Scene<'scene>, next: &Vertex) -> f32 {
+        match self.emitter() {
+        | Some(light) => {
+            let (area, _) = light.pdf_emit(&(next.p - self.p).normalize());
+            area / scene.lights().len() as f32
+        }
+        | None => 0.0,
+        }
+    }
+}
+
+/// Extend `path` along `ray`, which was sampled with solid angle density `pdf`,
+/// by sampling BxDFs until it holds `limit` vertices or escapes the scene.
+fn walk<'scene, S: sample::Sampler>(
+    scene: &scene::Scene<'scene>,
+    mut ray: math::Ray,
+    mut beta: math::Vec3,
+    mut pdf: f32,
+    limit: usize,
+    path: &mut Vec<Vertex<'scene>>,
+    sampler: &mut S,
+) {
+    let mut hit = geom::Hit::default();
+    while path.len() < limit && scene.hit(&mut ray, &mut hit) {
+        let prev = path.len() - 1;
+        let mut vertex = Vertex::surface(scene, &ray, &hit, beta);
+        vertex.fwd = path[prev].convert(pdf, &vertex);
+        path.push(vertex);
+
+        if path.len() == limit { break }
+
+        let bxdf = hit.bxdf.unwrap();
+        let bs = bxdf.sample(&vertex.wo, &hit.n, sampler);
+
+        if bs.p < math::EPSILON || bs.v.is_zero() {
+            break
+        }
+
+        beta *= bs.v * bs.d.dot(&hit.n).abs() / bs.p;
+
+        // Delta lobes can't be reached by any other strategy,
+        // so they drop out of the weights altogether
+        let rev = if bs.delta { 0.0 } else { bxdf.pdf(&bs.d, &vertex.wo, &hit.n) };
+        pdf = if bs.delta { 0.0 } else { bs.p };
+        path[prev + 1].delta = bs.delta;
+        path[prev].rev = vertex.convert(rev, &path[prev]);
+
+        ray = ray.spawn(hit.p, bs.d);
+    }
+}
+
+/// Subpaths traced for one camera ray, ready to be joined.
+struct Subpaths<'a, 'scene> {
+    scene: &'a scene::Scene<'scene>,
+    camera: &'a camera::Camera,
+
+    /// Camera ray, which shadow rays take their time from
+    ray: &'a math::Ray,
+
+    from_light: Vec<Vertex<'scene>>,
+    from_camera: Vec<Vertex<'scene>>,
+}
+
+impl<'a, 'scene> Subpaths<'a, 'scene> {
+    /// Contribution of the path made from the first `s` light and `t` camera vertices,
+    /// weighted against every other way of making it, along with the screen coordinates
+    /// it reaches if it was joined straight to the lens.
+    fn connect<S: sample::Sampler>(&self, s: usize, t: usize, sampler: &mut S) -> (math::Vec3, Option<(f32, f32)>) {
+        let none = (math::Vec3::default(), None);
+        let scene = self.scene;
+        let mut sampled = None;
+        let mut screen = None;
+
+        let l = if s == 0 {
+
+            // Camera subpath found an emitter on its own
+            let pt = &self.from_camera[t - 1];
+            match pt.kind {
+            | Kind::Surface(_, Some((emit, _))) => emit * pt.beta,
+            | _ => return none,
+            }
+
+        } else if t == 1 {
+
+            // Light subpath joined to a fresh point on the lens
+            let qs = &self.from_light[s - 1];
+            let lens = self.camera.lens_point(sampler);
+            let w = lens - qs.p;
+            let t = w.len();
+            let wi = w / t;
+            let d = -wi;
+            screen = match self.camera.project(&lens, &d) {
+            | Some(screen) => Some(screen),
+            | None => return none,
+            };
+
+            // Importance per pixel over the lens density, whose lens areas cancel out
+            let pixels = (scene.width() * scene.height()) as f32;
+            let importance = pixels * self.camera.pdf(&lens, &d) / (t * t);
+            let vertex = Vertex::new(Kind::Camera, lens, math::Vec3::default(), math::Vec3::broadcast(importance));
+
+            let l = qs.beta * qs.f(&vertex) * vertex.beta * qs.n.dot(&wi).abs();
+            if l.is_zero() || integrator::shadowed(scene, &self.ray.spawn(qs.p, wi), t) {
+                return none
+            }
+            sampled = Some(vertex);
+            l
+
+        } else if s == 1 {
+
+            // Camera subpath joined to a fresh point on a light
+            let pt = &self.from_camera[t - 1];
+            let lights = scene.lights();
+            if lights.is_empty() { return none }
+            let pick = 1.0 / lights.len() as f32;
+            let light = lights[((sampler.get_1d() * lights.len() as f32) as usize).min(lights.len() - 1)];
+            let ls = light.sample(&pt.p, sampler);
+
+            if ls.p <= 0.0 || ls.a <= 0.0 { return none }
+
+            let shadow = self.ray.spawn(pt.p, ls.d);
+            let beta = light.eval(&shadow) * ls.a / (ls.p * pick);
+            let mut vertex = Vertex::new(Kind::Light(light, ls.delta), pt.p + ls.d * ls.t, ls.n, beta);
+            vertex.fwd = vertex.pdf_origin(scene, pt);
+
+            let l = pt.beta * pt.f(&vertex) * vertex.beta * pt.n.dot(&ls.d).abs();
+            if l.is_zero() || integrator::shadowed(scene, &shadow, ls.t) {
+                return none
+            }
+            sampled = Some(vertex);
+            l
+
+        } else {
+
+            // Both subpaths joined by a shadow ray
+            let qs = &self.from_light[s - 1];
+            let pt = &self.from_camera[t - 1];
+            let l = qs.beta * qs.f(pt) * pt.f(qs) * pt.beta;
+            if l.is_zero() { return none }
+            let w = qs.p - pt.p;
+            let t = w.len();
+            let d = w / t;
+            let g = qs.n.dot(&d).abs() * pt.n.dot(&d).abs() / (t * t);
+            if integrator::shadowed(scene, &self.ray.spawn(pt.p, d), t) {
+                return none
+            }
+            l * g
+        };
+
+        if l.is_zero() { return none }
+
+        (l * self.weight(sampled, s, t), screen)
+    }
+
+    /// Power heuristic weight of joining the first `s` light and `t` camera vertices,
+    /// with `sampled` standing in for the endpoint that `connect` sampled afresh.
+    ///
+    /// Every other strategy for the same path is reached by shifting the join one
+    /// vertex at a time, which only changes the ratio of densities at that vertex.
+    fn weight(&self, sampled: Option<Vertex<'scene>>, s: usize, t: usize) -> f32 {
+        if s + t == 2 { return 1.0 }
+
+        let camera = self.camera;
+        let mut lv = self.from_light[..s].to_vec();
+        let mut cv = self.from_camera[..t].to_vec();
+        match sampled {
+        | Some(vertex) if s == 1 => lv[0] = vertex,
+        | Some(vertex) => cv[0] = vertex,
+        | None => (),
+        }
+
+        // Endpoints are joined rather than scattered through, whatever lobe they sampled
+        cv[t - 1].delta = false;
+        if s > 0 { lv[s - 1].delta = false }
+
+        let pt = cv[t - 1];
+        let qs = s.checked_sub(1).map(|i| lv[i]);
+        let pt_minus = t.checked_sub(2).map(|i| cv[i]);
+        let qs_minus = s.checked_sub(2).map(|i| lv[i]);
+
+        // Densities across the join, had the other subpath sampled them
+        cv[t - 1].rev = match &qs {
+        | Some(qs) => qs.pdf(camera, qs_minus.as_ref(), &pt),
+        | None => pt.pdf_origin(self.scene, pt_minus.as_ref().expect("[INTERNAL ERROR]: emitter seen from the lens")),
+        };
+        if let Some(pt_minus) = &pt_minus {
+            cv[t - 2].rev = match &qs {
+            | Some(qs) => pt.pdf(camera, Some(qs), pt_minus),
+            | None => pt.pdf_light(pt_minus),
+            };
+        }
+        if let Some(qs) = &qs {
+            lv[s - 1].rev = pt.pdf(camera, pt_minus.as_ref(), qs);
+            if let Some(qs_minus) = &qs_minus {
+                lv[s - 2].rev = qs.pdf(camera, Some(&pt), qs_minus);
+            }
+        }
+
+        // Zero densities only arise at delta vertices, which are skipped anyway
+        let remap = |pdf: f32| if pdf != 0.0 { pdf } else { 1.0 };
+        let mut sum = 0.0;
+
+        let mut ratio = 1.0;
+        for i in (1..t).rev() {
+            ratio *= remap(cv[i].rev) / remap(cv[i].fwd);
+            if !cv[i].delta && !cv[i - 1].delta {
+                sum += ratio * ratio;
+            }
+        }
+
+        let mut ratio = 1.0;
+        for i in (0..s).rev() {
+            ratio *= remap(lv[i].rev) / remap(lv[i].fwd);
+            let delta = if i > 0 { lv[i - 1].delta } else { lv[0].is_delta_light() };
+            if !lv[i].delta && !delta {
+                sum += ratio * ratio;
+            }
+        }
+
+        1.0 / (1.0 + sum)
+    }
+}
+
+impl<'scene> integrator::Integrator<'scene> for BDPT {
+    /// Joining subpaths needs the camera, which only `trace` is given.
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, _: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        panic!("[INTERNAL ERROR]: BDPT can only trace whole camera rays")
+    }
+
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        view: &integrator::View,
+        ray: &mut math::Ray,
+        _: &mut geom::Hit<'scene>,
+        sampler: &mut S,
+        splats: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+
+        // One more scattering vertex than `depth`, which `Path` reaches by sampling the lights
+        let depth = self.depth + 1;
+
+        let mut from_camera = Vec::with_capacity(depth + 2);
+        from_camera.push(Vertex::new(Kind::Camera, ray.p, math::Vec3::default(), math::Vec3::broadcast(1.0)));
+        let camera = view.camera;
+        let pdf = camera.pdf(&ray.p, &ray.d);
+        walk(scene, *ray, math::Vec3::broadcast(1.0), pdf, depth + 2, &mut from_camera, sampler);
+
+        let mut from_light = Vec::with_capacity(depth + 1);
+        let lights = scene.lights();
+        if !lights.is_empty() {
+            let pick = 1.0 / lights.len() as f32;
+            let light = lights[((sampler.get_1d() * lights.len() as f32) as usize).min(lights.len() - 1)];
+            let e = light.emit(sampler);
+            if e.area > 0.0 && e.dir > 0.0 && !e.v.is_zero() {
+                let mut vertex = Vertex::new(Kind::Light(light, e.delta), e.p, e.n, e.v);
+                vertex.fwd = e.area * pick;
+                from_light.push(vertex);
+                let cos = if e.n.is_zero() { 1.0 } else { e.n.dot(&e.d).abs() };
+                let beta = e.v * cos / (pick * e.area * e.dir);
+                walk(scene, ray.spawn(e.p, e.d), beta, e.dir, depth + 1, &mut from_light, sampler);
+            }
+        }
+
+        let paths = Subpaths { scene, camera, ray, from_light, from_camera };
+        let mut color = math::Vec3::default();
+
+        for t in 1..=paths.from_camera.len() {
+            for s in 0..=paths.from_light.len() {
+                if s + t < 2 || s + t - 2 > depth || (s == 1 && t == 1) {
+                    continue
+                }
+                let (l, screen) = paths.connect(s, t, sampler);
+                match screen {
+                | Some((u, v)) => {
+                    let x = ((u * scene.width() as f32) as usize).min(scene.width() - 1);
+                    let y = (((1.0 - v) * scene.height() as f32) as usize).min(scene.height() - 1);
+                    splats.push(film::Splat::new(x, y, l));
+                }
+                | None => color += l,
+                }
+            }
+        }
+
+        color
+    }
+}
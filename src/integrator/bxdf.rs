@@ -4,6 +4,7 @@ use crate::bxdf::BxDF as _;
 use crate::prelude::*;
 use crate::geom;
 use crate::math;
+use crate::sample;
 use crate::scene;
 use crate::integrator;
 
@@ -11,7 +12,7 @@ use crate::integrator;
 pub struct BxDF;
 
 impl<'scene> integrator::Integrator<'scene> for BxDF {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, depth: usize) -> math::Vec3 {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, depth: usize) -> math::Vec3 {
         let p = hit.p;
         let n = hit.n;  
         let wr = (ray.p - hit.p).normalize();
@@ -23,7 +24,7 @@ impl<'scene> integrator::Integrator<'scene> for BxDF {
             let wi = (l - p).normalize();
             let t = (l - p).len();
 
-            if integrator::shadowed(scene, &p, &wi, t) || n.dot(&wi) < 0.0 { continue }
+            if integrator::shadowed(scene, &ray.spawn(p, wi), t) || n.dot(&wi) < 0.0 { continue }
 
             color += hit.bxdf.unwrap().eval(&wi, &wr, &n)
                 / l.sub(&p).len_sq()
@@ -31,14 +32,14 @@ impl<'scene> integrator::Integrator<'scene> for BxDF {
                 * light.i;
         }
 
-        let bs = hit.bxdf.unwrap().sample(&wr, &n);
+        let bs = hit.bxdf.unwrap().sample(&wr, &n, sampler);
 
         let mut hit_record = geom::Hit::default();
-        let mut ray = math::Ray::new(p, bs.d);
+        let mut ray = ray.spawn(p, bs.d);
 
         if scene.hit(&mut ray, &mut hit_record) {
             if bs.delta {
-                color += self.shade(scene, &ray, &hit_record, depth + 1);
+                color += self.shade(scene, &ray, &hit_record, sampler, depth + 1);
             } else if let Some(light) = hit_record.emit {
                 color += light; 
             }
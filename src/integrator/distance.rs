@@ -0,0 +1,18 @@
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+/// Distance along the camera ray to the first hit, in every channel.
+///
+/// Distances are written as they are, so either save to a floating point
+/// format or lower the exposure to bring them into view.
+#[derive(Copy, Clone, Debug)]
+pub struct Distance;
+
+impl<'scene> integrator::Integrator<'scene> for Distance {
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, hit: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        math::Vec3::broadcast(hit.t)
+    }
+}
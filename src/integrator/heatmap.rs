@@ -0,0 +1,66 @@
+use crate::film;
+use crate::geom;
+use crate::image;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+use crate::stats;
+
+use crate::geom::Surface;
+
+/// False color for the work it takes to find what a camera ray hits,
+/// from blue (none) to red (`max` or more).
+///
+/// Work is measured with the counters in `stats`, so they must be enabled
+/// for the whole render; the command line does this when this integrator is selected.
+#[derive(Copy, Clone, Debug)]
+pub struct Heatmap {
+    work: Work,
+    max: f32,
+}
+
+/// Kind of work a heatmap shows.
+#[derive(Copy, Clone, Debug)]
+pub enum Work {
+    /// BVH nodes visited
+    Nodes,
+
+    /// Primitives tested for intersection
+    Tests,
+}
+
+impl Heatmap {
+    pub fn new(work: Work, max: f32) -> Self {
+        Heatmap { work, max }
+    }
+
+    /// Work of this heatmap's kind done so far on this thread.
+    fn work(&self) -> usize {
+        match self.work {
+        | Work::Nodes => stats::BVH_HITS.read_local() + stats::BVH_MISSES.read_local(),
+        | Work::Tests => stats::INTERSECTION_TESTS.read_local() - stats::BOUNDING_BOX_INTERSECTION_TESTS.read_local(),
+        }
+    }
+}
+
+impl<'scene> integrator::Integrator<'scene> for Heatmap {
+    /// Rays that miss take work too, which only `trace` sees.
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, _: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        panic!("[INTERNAL ERROR]: heatmap can only trace whole camera rays")
+    }
+
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        _: &integrator::View,
+        ray: &mut math::Ray,
+        hit: &mut geom::Hit<'scene>,
+        _: &mut S,
+        _: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+        let before = self.work();
+        scene.hit(ray, hit);
+        image::heat((self.work() - before) as f32 / self.max)
+    }
+}
@@ -2,6 +2,7 @@ use crate::prelude::*;
 use crate::geom;
 use crate::light::Light as _;
 use crate::math;
+use crate::sample;
 use crate::scene;
 use crate::integrator;
 
@@ -9,7 +10,7 @@ use crate::integrator;
 pub struct Light;
 
 impl<'scene> integrator::Integrator<'scene> for Light {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, depth: usize) -> math::Vec3 {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, depth: usize) -> math::Vec3 {
 
         if depth > 5 { return math::Vec3::default() }
 
@@ -21,26 +22,28 @@ impl<'scene> integrator::Integrator<'scene> for Light {
 
         for light in scene.lights() {
 
-            let ls = light.sample(&p);
+            let ls = light.sample(&p, sampler);
 
-            if integrator::shadowed(scene, &p, &ls.d, ls.t) { continue }
+            let shadow = ray.spawn(p, ls.d);
 
-            color += light.eval(&math::Ray::new(p, ls.d))
+            if integrator::shadowed(scene, &shadow, ls.t) { continue }
+
+            color += light.eval(&shadow)
                 * hit.bxdf.unwrap().eval(&ls.d, &wr, &n)
                 * ls.a
                 * n.dot(&ls.d)
                 / ls.p;
         }
 
-        let bs = hit.bxdf.unwrap().sample(&wr, &n);
+        let bs = hit.bxdf.unwrap().sample(&wr, &n, sampler);
 
         if bs.delta && bs.p > 0.001 {
             let mut hr = geom::Hit::default();
-            let mut recurse = math::Ray::new(p, bs.d);
+            let mut recurse = ray.spawn(p, bs.d);
 
             if !scene.hit(&mut recurse, &mut hr) { return color }
 
-            color += self.shade(scene, &recurse, &hr, depth + 1)
+            color += self.shade(scene, &recurse, &hr, sampler, depth + 1)
                 * bs.v
                 * n.dot(&bs.d).abs()
                 / bs.p
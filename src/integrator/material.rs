@@ -0,0 +1,21 @@
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+/// False color for the material of the first hit.
+///
+/// BxDFs are numbered in the order the scene defines them, so colors are the same
+/// from run to run, and surfaces share a color only if they share a material.
+#[derive(Copy, Clone, Debug)]
+pub struct Material;
+
+impl<'scene> integrator::Integrator<'scene> for Material {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, _: &math::Ray, hit: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        match hit.bxdf.and_then(|bxdf| scene.material(bxdf)) {
+        | Some(id) => integrator::false_color(id),
+        | None => math::Vec3::default(),
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+/// False color for the top-level surface of the first hit.
+///
+/// Surfaces are numbered in the order the scene declares them,
+/// so colors are the same from run to run.
+#[derive(Copy, Clone, Debug)]
+pub struct Object;
+
+impl<'scene> integrator::Integrator<'scene> for Object {
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, hit: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        integrator::false_color(hit.object)
+    }
+}
@@ -2,6 +2,7 @@ use crate::bxdf::BxDF;
 use crate::geom;
 use crate::light::Light;
 use crate::math;
+use crate::sample;
 use crate::scene;
 use crate::integrator;
 
@@ -26,7 +27,7 @@ impl Path {
 }
 
 impl<'scene> integrator::Integrator<'scene> for Path {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, _: usize) -> math::Vec3 {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &math::Ray, hit: &geom::Hit<'scene>, sampler: &mut S, _: usize) -> math::Vec3 {
 
         let mut beta = math::Vec3::broadcast(1.0);
         let mut color = math::Vec3::default();
@@ -34,16 +35,30 @@ impl<'scene> integrator::Integrator<'scene> for Path {
         let mut ray = ray.clone();
         let mut hit = hit.clone();
         let mut specular_bounce = false;
+        let mut bxdf_pdf = 0.0;
 
         for bounces in 0.. {
 
-            if bounces == 0 || specular_bounce {
-                if let Some(emit) = hit.emit {
+            // Emitters found by sampling the BxDF are weighted against
+            // the chance of having found them by sampling the lights
+            if let Some(emit) = hit.emit {
+                if bounces == 0 || specular_bounce {
                     color += emit * beta;
+                } else {
+                    let light_pdf = scene.lights()
+                        .iter()
+                        .map(|light| light.pdf(&ray))
+                        .sum::<f32>();
+                    color += emit * beta * integrator::power_heuristic(bxdf_pdf, light_pdf);
                 }
             }
 
             let d = (ray.p - hit.p).normalize();
+            let bxdf = hit.bxdf.unwrap();
+
+            // The BxDF isn't sampled past the last bounce, so the lights
+            // are the only strategy left to find emitters with
+            let last = bounces >= self.depth;
 
             // Sample lights
 
@@ -51,42 +66,57 @@ impl<'scene> integrator::Integrator<'scene> for Path {
 
             for light in scene.lights() {
 
-                let ls = light.sample(&hit.p);
+                let ls = light.sample(&hit.p, sampler);
+
+                if ls.p <= 0.0 || ls.a <= 0.0 { continue }
+
+                let shadow = ray.spawn(hit.p, ls.d);
 
-                if integrator::shadowed(scene, &hit.p, &ls.d, ls.t) { continue }
+                if integrator::shadowed(scene, &shadow, ls.t) { continue }
 
-                l += light.eval(&math::Ray::new(hit.p, ls.d))
-                    * hit.bxdf.unwrap().eval(&d, &ls.d, &hit.n)
+                let weight = if ls.delta || last {
+                    1.0
+                } else {
+                    integrator::power_heuristic(ls.p / ls.a, bxdf.pdf(&d, &ls.d, &hit.n))
+                };
+
+                l += light.eval(&shadow)
+                    * bxdf.eval(&d, &ls.d, &hit.n)
                     * ls.a
                     * hit.n.dot(&ls.d)
+                    * weight
                     / ls.p;
             }
 
             color += l * beta;
 
+            if last { break }
+
             // Sample BSDF
 
-            let bs = hit.bxdf.unwrap().sample(&d, &hit.n);
+            let bs = bxdf.sample(&d, &hit.n, sampler);
 
             if bs.p < math::EPSILON || bs.v.is_zero() {
                 break
             }
 
-            beta *= bs.v * d.dot(&hit.n).abs() / bs.p;
+            beta *= bs.v * bs.d.dot(&hit.n).abs() / bs.p;
 
             specular_bounce = bs.delta;
+            bxdf_pdf = bs.p;
 
-            // Russian roulette termination
+            // Russian roulette termination, by the strongest channel so that
+            // paths still carrying light in any channel survive in proportion
 
             if bounces > 3 {
-                let q = math::max(0.05, 1.0 - beta.y());
-                if rand::random::<f32>() < q { break }
+                let q = math::max(0.05, 1.0 - beta.max_horizontal());
+                if sampler.get_1d() < q { break }
                 beta /= 1.0 - q;
             }
 
-            ray = math::Ray::new(hit.p, bs.d);
+            ray = ray.spawn(hit.p, bs.d);
 
-            if bounces >= self.depth || beta.y() > self.threshold || !scene.hit(&mut ray, &mut hit) {
+            if beta.y() > self.threshold || !scene.hit(&mut ray, &mut hit) {
                 break
             }
         }
@@ -0,0 +1,311 @@
+use std::sync;
+
+use rayon::prelude::*;
+
+use crate::bxdf;
+use crate::bxdf::BxDF;
+use crate::film;
+use crate::geom;
+use crate::integrator;
+use crate::kdtree;
+use crate::light::Light;
+use crate::math;
+use crate::sample;
+use crate::sample::Sampler;
+use crate::scene;
+
+use crate::geom::Surface;
+
+/// Photon mapper.
+///
+/// Before rendering, photons are traced from the lights and stored wherever
+/// they land on a surface that scatters diffusely, after at least one bounce.
+/// Camera rays follow delta lobes until they reach such a surface, then sample
+/// the lights for direct lighting and estimate everything else from the
+/// density of photons within `radius`. Caustics, which no camera path can
+/// find when the light is a point or is only seen through glass, come
+/// straight from the photons.
+///
+/// The progressive variant emits a fresh photon map for every sample, each
+/// gathered with a smaller radius than the last, so that the average over
+/// samples converges to the right answer rather than a blurred one.
+#[derive(Copy, Clone, Debug)]
+pub struct Photon {
+    /// Maximum number of bounces, for both camera rays and photons
+    depth: usize,
+
+    /// Photons emitted per photon map
+    count: usize,
+
+    /// Gather radius, or the radius of the first sample when progressive
+    radius: f32,
+
+    /// Fraction of photons kept between progressive samples
+    alpha: Option<f32>,
+}
+
+impl Photon {
+    pub fn new(depth: usize, count: usize, radius: f32) -> Self {
+        Photon { depth, count, radius, alpha: None }
+    }
+
+    pub fn progressive(depth: usize, count: usize, radius: f32, alpha: f32) -> Self {
+        Photon { depth, count, radius, alpha: Some(alpha) }
+    }
+
+    /// Gather radius for sample `index`.
+    ///
+    /// Progressive photon mapping shrinks the area of the radius by (i + alpha) / (i + 1)
+    /// after each sample i, counting from one, which shrinks it just slowly enough
+    /// for the variance of the average to still vanish.
+    fn radius(&self, index: usize) -> f32 {
+        match self.alpha {
+        | None => self.radius,
+        | Some(alpha) => {
+            let shrink = (1..=index)
+                .map(|i| (i as f32 + alpha) / (i as f32 + 1.0))
+                .product::<f32>();
+            self.radius * shrink.sqrt()
+        }
+        }
+    }
+
+    /// Trace `count` photons from the lights for sample `index` of `frame`.
+    ///
+    /// Photons are traced in parallel, each from its own seed, so the map
+    /// does not depend on the number of threads.
+    fn emit(&self, scene: &scene::Scene, frame: usize, index: usize, seed: u64) -> PhotonMap {
+        let photons = (0..self.count)
+            .into_par_iter()
+            .map_init(geom::Hit::default, |hit, photon| self.trace_photon(scene, frame, seed, photon, hit))
+            .flatten_iter()
+            .collect();
+
+        PhotonMap {
+            tree: kdtree::Tree::new(photons),
+            emitted: self.count,
+            radius: self.radius(index),
+        }
+    }
+
+    /// Trace photon number `photon` from the lights, returning where it was stored.
+    fn trace_photon<'scene>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        frame: usize,
+        seed: u64,
+        photon: usize,
+        hit: &mut geom::Hit<'scene>,
+    ) -> Vec<Stored> {
+        let mut photons = Vec::new();
+        let lights = scene.lights();
+        if lights.is_empty() { return photons }
+
+        let mut sampler = sample::Independent::default();
+        sampler.set_seed(seed);
+        sampler.start_pixel(photon, 0, self.count);
+        sampler.start_sample(0);
+
+        let pick = 1.0 / lights.len() as f32;
+        let light = lights[((sampler.get_1d() * lights.len() as f32) as usize).min(lights.len() - 1)];
+        let e = light.emit(&mut sampler);
+        if e.area <= 0.0 || e.dir <= 0.0 || e.v.is_zero() { return photons }
+
+        let cos = if e.n.is_zero() { 1.0 } else { e.n.dot(&e.d).abs() };
+        let mut power = e.v * cos / (pick * e.area * e.dir);
+        let time = scene.shutter().sample(frame, &mut sampler);
+        let mut ray = math::Ray::new(e.p, e.d).with_time(time);
+
+        for bounce in 0..self.depth {
+            if !scene.hit(&mut ray, hit) { break }
+
+            let wi = -ray.d;
+            let bxdf = hit.bxdf.unwrap();
+
+            // Direct lighting is found by sampling the lights instead
+            if bounce > 0 && !bxdf.is_delta() {
+                photons.push(Stored { p: hit.p, wi, power });
+            }
+
+            let bs = bxdf.sample(&wi, &hit.n, &mut sampler);
+
+            if bs.p < math::EPSILON || bs.v.is_zero() {
+                break
+            }
+
+            // Russian roulette on the change in power, so that
+            // surviving photons all carry about as much as before
+            let next = power * bs.v * bs.d.dot(&hit.n).abs() / bs.p;
+            let q = math::max(0.0, 1.0 - next.max_horizontal() / power.max_horizontal());
+            if sampler.get_1d() < q { break }
+            power = next / (1.0 - q);
+
+            ray = ray.spawn(hit.p, bs.d);
+        }
+
+        photons
+    }
+
+    /// Radiance reaching `hit` straight from the lights, scattered toward `d`.
+    fn direct<'scene, S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        ray: &math::Ray,
+        hit: &geom::Hit<'scene>,
+        d: &math::Vec3,
+        sampler: &mut S,
+    ) -> math::Vec3 {
+        let bxdf = hit.bxdf.unwrap();
+        let mut l = math::Vec3::default();
+        for light in scene.lights() {
+            let ls = light.sample(&hit.p, sampler);
+
+            if ls.p <= 0.0 || ls.a <= 0.0 { continue }
+
+            let shadow = ray.spawn(hit.p, ls.d);
+
+            if integrator::shadowed(scene, &shadow, ls.t) { continue }
+
+            l += light.eval(&shadow)
+                * bxdf.eval(d, &ls.d, &hit.n)
+                * ls.a
+                * hit.n.dot(&ls.d)
+                / ls.p;
+        }
+        l
+    }
+}
+
+/// Photon left on a surface.
+#[derive(Copy, Clone, Debug)]
+struct Stored {
+    /// Position
+    p: math::Vec3,
+
+    /// Direction back toward where the photon came from
+    wi: math::Vec3,
+
+    /// Power carried, before sharing it out over every photon emitted
+    power: math::Vec3,
+}
+
+impl kdtree::Point for Stored {
+    fn position(&self) -> math::Vec3 {
+        self.p
+    }
+}
+
+/// Photons traced for one sample, ready to be gathered.
+#[derive(Clone, Debug)]
+pub struct PhotonMap {
+    tree: kdtree::Tree<Stored>,
+
+    /// Number of photons emitted, including those never stored
+    emitted: usize,
+
+    /// Gather radius
+    radius: f32,
+}
+
+impl PhotonMap {
+    /// Radiance that `bxdf` scatters toward `d` at `hit`, estimated from
+    /// the photons within the gather radius.
+    fn gather(&self, hit: &geom::Hit, d: &math::Vec3, bxdf: &bxdf::Any) -> math::Vec3 {
+        if self.tree.is_empty() { return math::Vec3::default() }
+        let mut sum = math::Vec3::default();
+        self.tree.within(&hit.p, self.radius, |photon| {
+            sum += bxdf.eval(d, &photon.wi, &hit.n) * photon.power;
+        });
+        sum / (self.emitted as f32 * math::PI * self.radius * self.radius)
+    }
+}
+
+/// Most recent photon map built for a scene, which every tile of a pass shares.
+#[derive(Debug, Default)]
+pub struct PhotonCache(sync::Mutex<Option<(u64, sync::Arc<PhotonMap>)>>);
+
+impl PhotonCache {
+    /// Photon map for `key`, built with `build` unless it is the one already cached.
+    ///
+    /// The lock is not held while building, since `build` runs on the thread pool
+    /// that tiles render on. Callers that miss at the same time each build their own,
+    /// which [`Scene::trace_photons`](scene::Scene::trace_photons) avoids by building
+    /// every map a pass needs before its tiles start.
+    pub fn get<F>(&self, key: u64, build: F) -> sync::Arc<PhotonMap> where F: FnOnce() -> PhotonMap {
+        if let Some((cached, map)) = &*self.lock() {
+            if *cached == key { return map.clone() }
+        }
+        let map = sync::Arc::new(build());
+        *self.lock() = Some((key, map.clone()));
+        map
+    }
+
+    fn lock(&self) -> sync::MutexGuard<'_, Option<(u64, sync::Arc<PhotonMap>)>> {
+        self.0.lock().expect("[INTERNAL ERROR]: photon cache lock poisoned")
+    }
+}
+
+impl<'scene> integrator::Integrator<'scene> for Photon {
+    /// Gathering needs the photon map, which only `trace` is given.
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, _: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        panic!("[INTERNAL ERROR]: photon mapping can only trace whole camera rays")
+    }
+
+    fn trace<S: sample::Sampler>(
+        &self,
+        scene: &scene::Scene<'scene>,
+        view: &integrator::View,
+        ray: &mut math::Ray,
+        hit: &mut geom::Hit<'scene>,
+        sampler: &mut S,
+        _: &mut Vec<film::Splat>,
+    ) -> math::Vec3 {
+        let photons = view.photons.expect("[INTERNAL ERROR]: photon mapping without a photon map");
+        let mut beta = math::Vec3::broadcast(1.0);
+        let mut color = math::Vec3::default();
+
+        if !scene.hit(ray, hit) { return color }
+
+        for bounce in 0.. {
+
+            // Only delta lobes lead here, which no light sample can find
+            if let Some(emit) = hit.emit {
+                color += emit * beta;
+            }
+
+            let d = -ray.d;
+            let bxdf = hit.bxdf.unwrap();
+
+            if !bxdf.is_delta() {
+                color += (self.direct(scene, ray, hit, &d, sampler) + photons.gather(hit, &d, bxdf)) * beta;
+            }
+
+            if bounce >= self.depth { break }
+
+            // Whatever isn't delta was just gathered, so only delta lobes carry on
+            let bs = bxdf.sample(&d, &hit.n, sampler);
+
+            if !bs.delta || bs.p < math::EPSILON || bs.v.is_zero() {
+                break
+            }
+
+            beta *= bs.v * bs.d.dot(&hit.n).abs() / bs.p;
+
+            *ray = ray.spawn(hit.p, bs.d);
+
+            if !scene.hit(ray, hit) { break }
+        }
+
+        color
+    }
+
+    fn photons(&self, scene: &scene::Scene<'scene>, frame: usize, index: usize) -> Option<sync::Arc<PhotonMap>> {
+        let index = if self.alpha.is_some() { index } else { 0 };
+        let seed = sample::hash(&[scene.seed(), frame as u64, index as u64]);
+        Some(scene.photons().get(seed, || self.emit(scene, frame, index, seed)))
+    }
+
+    fn per_sample(&self) -> bool {
+        self.alpha.is_some()
+    }
+}
@@ -5,13 +5,14 @@ use crate::light::Light;
 use crate::geom;
 use crate::integrator;
 use crate::math::{Ray, Vec3};
+use crate::sample;
 use crate::scene;
 
 #[derive(Copy, Clone, Debug)]
 pub struct Point;
 
 impl<'scene> integrator::Integrator<'scene> for Point {
-    fn shade(&self, scene: &scene::Scene<'scene>, ray: &Ray, hit: &geom::Hit<'scene>, _: usize) -> Vec3 {
+    fn shade<S: sample::Sampler>(&self, scene: &scene::Scene<'scene>, ray: &Ray, hit: &geom::Hit<'scene>, _: &mut S, _: usize) -> Vec3 {
 
         let p = hit.p;
         let n = hit.n;
@@ -25,7 +26,7 @@ impl<'scene> integrator::Integrator<'scene> for Point {
             let wi = (l - p).normalize();
             let t = (l - p).len();
 
-            if integrator::shadowed(scene, &p, &wi, t) || n.dot(&wi) < 0.0 { continue }
+            if integrator::shadowed(scene, &ray.spawn(p, wi), t) || n.dot(&wi) < 0.0 { continue }
 
             color += hit.bxdf.unwrap().eval(&wi, &wr, &n)
                 / l.sub(&p).len_sq()
@@ -0,0 +1,16 @@
+use crate::geom;
+use crate::integrator;
+use crate::math;
+use crate::sample;
+use crate::scene;
+
+/// Texture coordinates of the first hit in red and green, which
+/// are barycentric coordinates for triangles.
+#[derive(Copy, Clone, Debug)]
+pub struct UV;
+
+impl<'scene> integrator::Integrator<'scene> for UV {
+    fn shade<S: sample::Sampler>(&self, _: &scene::Scene<'scene>, _: &math::Ray, hit: &geom::Hit<'scene>, _: &mut S, _: usize) -> math::Vec3 {
+        math::Vec3::new(hit.u, hit.v, 0.0)
+    }
+}
@@ -0,0 +1,88 @@
+use std::cmp;
+
+use crate::geom;
+use crate::math;
+
+/// Item that a kd-tree can find by position.
+pub trait Point {
+    fn position(&self) -> math::Vec3;
+}
+
+/// Balanced kd-tree stored as a flat array.
+///
+/// Each range of the array is split at its middle element, which holds the
+/// median of the range along whichever axis its points spread widest, with
+/// the points before and after it forming the two subtrees.
+#[derive(Clone, Debug)]
+pub struct Tree<T> {
+    points: Vec<T>,
+    axes: Vec<math::Axis>,
+}
+
+impl<T: Point> Tree<T> {
+    pub fn new(mut points: Vec<T>) -> Self {
+        let mut axes = vec![math::Axis::X; points.len()];
+        build(&mut points, &mut axes);
+        Tree { points, axes }
+    }
+
+    pub fn len(&self) -> usize {
+        self.points.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    /// Call `f` on every point no further than `radius` from `p`.
+    pub fn within<F: FnMut(&T)>(&self, p: &math::Vec3, radius: f32, mut f: F) {
+        self.search(0, self.points.len(), p, radius * radius, &mut f);
+    }
+
+    fn search<F: FnMut(&T)>(&self, lo: usize, hi: usize, p: &math::Vec3, r2: f32, f: &mut F) {
+        if lo >= hi { return }
+
+        let mid = (lo + hi) / 2;
+        let point = &self.points[mid];
+        let q = point.position();
+        if (q - *p).len_sq() <= r2 {
+            f(point);
+        }
+
+        // Descend into the side holding `p` first, and only cross
+        // the splitting plane if the search sphere does too
+        let axis = self.axes[mid] as usize;
+        let delta = p.get(axis) - q.get(axis);
+        let ((near_lo, near_hi), (far_lo, far_hi)) = if delta < 0.0 {
+            ((lo, mid), (mid + 1, hi))
+        } else {
+            ((mid + 1, hi), (lo, mid))
+        };
+        self.search(near_lo, near_hi, p, r2, f);
+        if delta * delta <= r2 {
+            self.search(far_lo, far_hi, p, r2, f);
+        }
+    }
+}
+
+fn build<T: Point>(points: &mut [T], axes: &mut [math::Axis]) {
+    if points.is_empty() { return }
+
+    let bound = points.iter()
+        .map(Point::position)
+        .fold(geom::Box3::default(), |bound, p| bound.union_v(&p));
+    let axis = bound.max_extent();
+    let mid = points.len() / 2;
+
+    points.select_nth_unstable_by(mid, |a, b| {
+        let a = a.position().get(axis as usize);
+        let b = b.position().get(axis as usize);
+        a.partial_cmp(&b).unwrap_or(cmp::Ordering::Equal)
+    });
+    axes[mid] = axis;
+
+    let (points_lo, points_hi) = points.split_at_mut(mid);
+    let (axes_lo, axes_hi) = axes.split_at_mut(mid);
+    build(points_lo, axes_lo);
+    build(&mut points_hi[1..], &mut axes_hi[1..]);
+}
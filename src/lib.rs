@@ -1,15 +1,33 @@
 /// Memory allocation
 pub mod arena;
 
+/// Variance-driven sample allocation
+pub mod adaptive;
+
 /// Bounding volume hierarchy
 pub mod bvh;
 
 /// Camera model
 pub mod camera;
 
+/// Resumable render state
+pub mod checkpoint;
+
+/// Image file output
+pub mod image;
+
+/// Radiance accumulation
+pub mod film;
+
+/// Pixel reconstruction filters
+pub mod filter;
+
 /// Algorithms for shading surfaces
 pub mod integrator;
 
+/// Nearest neighbor search
+pub mod kdtree;
+
 /// Lighting primitives
 pub mod light;
 
@@ -25,12 +43,21 @@ pub mod progress;
 /// Geometric primitives
 pub mod math;
 
+/// Distributed rendering over TCP
+pub mod remote;
+
 /// Full scene information
 pub mod scene;
 
-/// Sobol sampling
+/// Tile scheduling
+pub mod schedule;
+
+/// Sample generation
 pub mod sample;
 
+/// Tone mapping and display encoding
+pub mod tone;
+
 /// Intersection and shape models
 pub mod geom;
 
@@ -39,7 +66,9 @@ pub mod stats;
 
 pub mod prelude {
     pub use crate::bxdf::BxDF;
+    pub use crate::filter::Filter;
     pub use crate::integrator::Integrator;
     pub use crate::light::Light;
+    pub use crate::sample::Sampler;
     pub use crate::geom::Surface;
 }
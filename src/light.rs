@@ -2,6 +2,7 @@ use std::fmt;
 
 use crate::geom;
 use crate::math;
+use crate::sample;
 
 mod point;
 mod quad;
@@ -20,14 +21,58 @@ pub struct Sample {
     /// Attenuation
     pub a: f32,
 
-    /// Probability
+    /// Probability density with respect to area on the light
     pub p: f32,
+
+    /// Surface normal at the sampled point, or zero for point lights
+    pub n: math::Vec3,
+
+    /// Whether this sample came from a delta distribution
+    pub delta: bool,
+}
+
+/// Ray of light leaving an emitter, for tracing paths outward from the lights.
+#[readonly::make]
+#[derive(Copy, Clone, Debug, Default)]
+pub struct Emission {
+    /// Origin on the light
+    pub p: math::Vec3,
+
+    /// Surface normal at the origin, or zero for point lights
+    pub n: math::Vec3,
+
+    /// Direction of travel
+    pub d: math::Vec3,
+
+    /// Emitted radiance, or intensity for point lights
+    pub v: math::Vec3,
+
+    /// Probability density of the origin with respect to area on the light
+    pub area: f32,
+
+    /// Probability density of the direction with respect to solid angle
+    pub dir: f32,
+
+    /// Whether the origin came from a delta distribution
+    pub delta: bool,
 }
 
 pub trait Light: fmt::Debug + Send + Sync {
     fn eval(&self, ray: &math::Ray) -> math::Vec3;
-    fn sample(&self, point: &math::Vec3) -> Sample;
+    fn sample<S: sample::Sampler>(&self, point: &math::Vec3, sampler: &mut S) -> Sample;
+
+    /// Solid angle density of `sample` choosing direction `ray.d` from `ray.p`,
+    /// or zero if the ray misses the light before `ray.max`.
     fn pdf(&self, ray: &math::Ray) -> f32;
+
+    fn emit<S: sample::Sampler>(&self, sampler: &mut S) -> Emission;
+
+    /// Densities with which `emit` picks its origin, per unit area,
+    /// and direction `d`, per unit solid angle.
+    ///
+    /// Every light emits uniformly over its surface, so the origin itself doesn't matter.
+    fn pdf_emit(&self, d: &math::Vec3) -> (f32, f32);
+
     fn downcast_point(&self) -> Option<Point>;
 }
 
@@ -36,14 +81,22 @@ impl<'a, L> Light for &'a L where L: Light + ?Sized {
         (*self).eval(ray)
     }
 
-    fn sample(&self, point: &math::Vec3) -> Sample {
-        (*self).sample(point)
+    fn sample<S: sample::Sampler>(&self, point: &math::Vec3, sampler: &mut S) -> Sample {
+        (*self).sample(point, sampler)
     }
 
     fn pdf(&self, ray: &math::Ray) -> f32 {
         (*self).pdf(ray)
     }
 
+    fn emit<S: sample::Sampler>(&self, sampler: &mut S) -> Emission {
+        (*self).emit(sampler)
+    }
+
+    fn pdf_emit(&self, d: &math::Vec3) -> (f32, f32) {
+        (*self).pdf_emit(d)
+    }
+
     fn downcast_point(&self) -> Option<Point> {
         (*self).downcast_point()
     }
@@ -63,10 +116,10 @@ impl<'scene> Light for Any<'scene> {
         }
     }
 
-    fn sample(&self, point: &math::Vec3) -> Sample {
+    fn sample<S: sample::Sampler>(&self, point: &math::Vec3, sampler: &mut S) -> Sample {
         match self {
-            Any::Point(light) => light.sample(point),
-            Any::Quad(light) => light.sample(point),
+            Any::Point(light) => light.sample(point, sampler),
+            Any::Quad(light) => light.sample(point, sampler),
         }
     }
 
@@ -77,6 +130,20 @@ impl<'scene> Light for Any<'scene> {
         }
     }
 
+    fn emit<S: sample::Sampler>(&self, sampler: &mut S) -> Emission {
+        match self {
+            Any::Point(light) => light.emit(sampler),
+            Any::Quad(light) => light.emit(sampler),
+        }
+    }
+
+    fn pdf_emit(&self, d: &math::Vec3) -> (f32, f32) {
+        match self {
+            Any::Point(light) => light.pdf_emit(d),
+            Any::Quad(light) => light.pdf_emit(d),
+        }
+    }
+
     fn downcast_point(&self) -> Option<Point> {
         match self {
             Any::Point(light) => light.downcast_point(),
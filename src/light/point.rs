@@ -1,5 +1,6 @@
 use crate::math;
 use crate::light;
+use crate::sample;
 
 #[readonly::make]
 #[derive(Copy, Clone, Debug)]
@@ -25,17 +26,41 @@ impl light::Light for Point {
         self.i    
     }
 
-    fn sample(&self, p: &math::Vec3) -> light::Sample {
+    fn sample<S: sample::Sampler>(&self, p: &math::Vec3, _: &mut S) -> light::Sample {
         light::Sample {
             d: (self.p - p).normalize(),
             t: (self.p - p).len(),
             a: 1.0 / (self.p - p).len_sq(),
             p: 1.0,
+            n: math::Vec3::default(),
+            delta: true,
         }
     }
 
+    /// Rays can never hit a point, so only `sample` can find it.
     fn pdf(&self, _: &math::Ray) -> f32 {
-        1.0
+        0.0
+    }
+
+    /// Uniform over every direction.
+    fn emit<S: sample::Sampler>(&self, sampler: &mut S) -> light::Emission {
+        let (u, v) = sampler.get_2d();
+        let z = 1.0 - 2.0 * u;
+        let s = math::max(0.0, 1.0 - z * z).sqrt();
+        light::Emission {
+            p: self.p,
+            n: math::Vec3::default(),
+            d: math::Vec3::new(s * (math::TAU * v).cos(), s * (math::TAU * v).sin(), z),
+            v: self.i,
+            area: 1.0,
+            dir: 1.0 / (2.0 * math::TAU),
+            delta: true,
+        }
+    }
+
+    /// No ray can find a point from elsewhere, so only the direction has a density.
+    fn pdf_emit(&self, _: &math::Vec3) -> (f32, f32) {
+        (0.0, 1.0 / (2.0 * math::TAU))
     }
 
     fn downcast_point(&self) -> Option<Point> {
@@ -1,6 +1,7 @@
 use crate::geom;
 use crate::light;
 use crate::math;
+use crate::sample;
 
 impl<'scene> light::Light for geom::Quad<'scene> {
     fn eval(&self, ray: &math::Ray) -> math::Vec3 {
@@ -11,22 +12,54 @@ impl<'scene> light::Light for geom::Quad<'scene> {
         }
     }
 
-    fn sample(&self, p: &math::Vec3) -> light::Sample {
+    fn sample<S: sample::Sampler>(&self, p: &math::Vec3, sampler: &mut S) -> light::Sample {
+        let (u, v) = sampler.get_2d();
         let l = self.p
-            + self.u * rand::random::<f32>()
-            + self.v * rand::random::<f32>();
+            + self.u * u
+            + self.v * v;
         let delta = l - p;
         let wi = delta.normalize();
         light::Sample {
             d: wi,
             t: delta.len(),
             a: wi.dot(&self.n).abs() / delta.len_sq(),
-            p: 1.0 / (self.u.len() * self.v.len()),
+            p: 1.0 / self.area(),
+            n: self.n,
+            delta: false,
         }
     }
 
-    fn pdf(&self, _: &math::Ray) -> f32 {
-        1.0 / (self.u.len() * self.v.len())
+    /// Converts the uniform density over the quad's area to solid angle
+    /// at `ray.p`, which grows with distance and grazing angle.
+    fn pdf(&self, ray: &math::Ray) -> f32 {
+        match self.intersect(ray) {
+        | Some((t, _, _)) => {
+            let cos = ray.d.dot(&self.n).abs();
+            if cos > 0.0 { t * t / (cos * self.area()) } else { 0.0 }
+        }
+        | None => 0.0,
+        }
+    }
+
+    /// Cosine-weighted about the normal, from the side that `eval` sees lit.
+    fn emit<S: sample::Sampler>(&self, sampler: &mut S) -> light::Emission {
+        let (u, v) = sampler.get_2d();
+        let local = math::cosine_sphere(sampler);
+        let (x, y) = math::basis(&self.n);
+        let d = (self.n * local.z() + x * local.x() + y * local.y()).normalize();
+        light::Emission {
+            p: self.p + self.u * u + self.v * v,
+            n: self.n,
+            d,
+            v: self.emit.expect("Must be emitter to be in scene as a light"),
+            area: 1.0 / self.area(),
+            dir: math::max(0.0, d.dot(&self.n)) / math::PI,
+            delta: false,
+        }
+    }
+
+    fn pdf_emit(&self, d: &math::Vec3) -> (f32, f32) {
+        (1.0 / self.area(), math::max(0.0, d.dot(&self.n)) / math::PI)
     }
 
     fn downcast_point(&self) -> Option<light::Point> {
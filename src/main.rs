@@ -1,36 +1,384 @@
 use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::io;
+use std::net;
+use std::path;
+use std::process;
+use std::thread;
+use std::time;
 
 use photon::arena;
+use photon::checkpoint;
+use photon::film;
+use photon::film::Sink as _;
+use photon::image;
+use photon::integrator;
+use photon::progress;
+use photon::remote;
 use photon::scene;
+use photon::stats;
 
-#[cfg(feature = "progress")]
-use photon::progress;
+mod cli;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let args = env::args().collect::<Vec<_>>();
+    let options = match cli::Command::parse(env::args().skip(1)) {
+    | Ok(cli::Command::Render(options)) => options,
+    | Ok(cli::Command::Serve(server)) => return serve(&server),
+    | Err(cli::Error::Help) => {
+        print!("{}", cli::USAGE);
+        return Ok(())
+    }
+    | Err(error) => {
+        eprintln!("[CLI ERROR]: {}\n\n{}", error, cli::USAGE);
+        process::exit(2);
+    }
+    };
+
+    if let Some(threads) = options.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
     let arena = arena::Arena::default();
-    let scene = scene::Scene::load(&arena, &args[1])?;
+    let mut scene = match scene::Scene::load(&arena, &options.scene) {
+    | Ok(scene) => scene,
+    | Err(error) => {
+        eprintln!("[SCENE ERROR]: {}", error);
+        process::exit(1);
+    }
+    };
+
+    if let Some(width) = options.width { scene.set_width(width); }
+    if let Some(height) = options.height { scene.set_height(height); }
+    if options.width.is_some() || options.height.is_some() {
+        scene.set_aspect(scene.width() as f32 / scene.height() as f32);
+    }
+    if let Some(samples) = options.samples { scene.set_samples(samples); }
+
+    if let Some(spec) = &options.integrator {
+        scene.set_integrator(parse_spec(&arena, "--integrator", spec, scene::Parser::parse_integrator));
+    }
+
+    // The heatmap integrator reads its work from the statistics counters
+    if options.stats || matches!(scene.integrator(), integrator::Any::Heatmap(_)) {
+        stats::enable();
+    }
+
+    let mut tone = *scene.tone();
+    if let Some(spec) = &options.tonemap {
+        tone.operator = parse_spec(&arena, "--tonemap", spec, scene::Parser::parse_tonemap);
+    }
+    if let Some(exposure) = options.exposure {
+        tone.exposure = exposure;
+    }
+    if let Some(spec) = &options.transfer {
+        tone.transfer = parse_spec(&arena, "--transfer", spec, scene::Parser::parse_transfer);
+    }
+    scene.set_tone(tone);
+
+    if let Some(spec) = &options.sampler {
+        scene.set_sampler(parse_spec(&arena, "--sampler", spec, scene::Parser::parse_sampler));
+    }
+
+    if let Some(seed) = options.seed { scene.set_seed(seed); }
+
+    if let Some(spec) = &options.adaptive {
+        scene.set_adaptive(Some(parse_spec(&arena, "--adaptive", spec, scene::Parser::parse_adaptive)));
+    }
+
+    if let Some(spec) = &options.tiles {
+        scene.set_schedule(parse_spec(&arena, "--tiles", spec, scene::Parser::parse_schedule));
+    }
+
+    if let Some(spec) = &options.crop {
+        scene.set_crop(Some(parse_spec(&arena, "--crop", spec, scene::Parser::parse_crop)));
+    }
+
+    if let Some(spec) = &options.filter {
+        scene.set_filter(parse_spec(&arena, "--filter", spec, scene::Parser::parse_filter));
+    }
+
+    if let Some(spec) = &options.shutter {
+        scene.set_shutter(parse_spec(&arena, "--shutter", spec, scene::Parser::parse_shutter));
+    }
+
+    let shots = match &options.camera {
+    | None => (0..scene.shots().len()).collect::<Vec<_>>(),
+    | Some(name) => match scene.shots().iter().position(|shot| shot.name.as_ref() == Some(name)) {
+        | Some(shot) => vec![shot],
+        | None => {
+            eprintln!("[CLI ERROR]: --camera: no camera named '{}'", name);
+            process::exit(2);
+        }
+    },
+    };
+    let (first, last) = options.frames.unwrap_or((1, scene.frames()));
+    if last > scene.frames() {
+        eprintln!("[CLI ERROR]: --frames: scene only has {} frame(s)", scene.frames());
+        process::exit(2);
+    }
+
+    let mut coordinator = None;
+    for shot in shots {
+        for frame in first..=last {
+            scene.select(shot, frame);
+
+            // Number images only when the scene has more than one
+            let name = match scene.shots().len() {
+            | 1 => None,
+            | _ => scene.shots()[shot].name.clone(),
+            };
+            let frame = match scene.frames() {
+            | 1 => None,
+            | _ => Some(frame),
+            };
+            let number = |path: &path::PathBuf| numbered(path, name.as_deref(), frame);
+            let paths = Paths {
+                output: number(&options.output),
+                heatmap: options.heatmap.as_ref().map(number),
+                merge: options.merge.as_ref().map(number),
+                save: options.save.as_ref().map(number),
+                resume: options.resume.as_ref().map(number),
+            };
+
+            if options.progress && (name.is_some() || frame.is_some()) {
+                println!("{}", paths.output.display());
+            }
+            render(&options, &paths, &mut scene, &mut coordinator)?;
+        }
+    }
 
-    #[cfg(feature = "progress")]
-    let progress = std::thread::spawn({
-        let pixels = scene.width() * scene.height();
-        move || progress::run(pixels)
-    });
+    if options.stats {
+        stats::print();
+    }
+
+    Ok(())
+}
+
+/// Per-image output and checkpoint files.
+struct Paths {
+    output: path::PathBuf,
+    heatmap: Option<path::PathBuf>,
+    merge: Option<path::PathBuf>,
+    save: Option<path::PathBuf>,
+    resume: Option<path::PathBuf>,
+}
 
-    scene.render();
+/// Insert a shot name and frame number before the extension of `path`,
+/// e.g. `out.png` becomes `out_front_0001.png`.
+fn numbered(path: &path::Path, name: Option<&str>, frame: Option<usize>) -> path::PathBuf {
+    let mut stem = path.file_stem().unwrap_or_default().to_owned();
+    if let Some(name) = name {
+        stem.push(format!("_{}", name));
+    }
+    if let Some(frame) = frame {
+        stem.push(format!("_{:04}", frame));
+    }
+    if let Some(extension) = path.extension() {
+        stem.push(".");
+        stem.push(extension);
+    }
+    path.with_file_name(stem)
+}
+
+/// Render the currently selected shot and frame of `scene`, connecting to
+/// remote workers on first use if any were given.
+fn render(
+    options: &cli::Options,
+    paths: &Paths,
+    scene: &mut scene::Scene,
+    coordinator: &mut Option<remote::Coordinator>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (mut film, cursor) = match &paths.resume {
+    | None => (film::Film::new(scene.width(), scene.height()), scene::Cursor::default()),
+    | Some(path) => match checkpoint::Checkpoint::load(path, scene) {
+        | Ok(checkpoint) => {
+            scene.set_seed(checkpoint.seed);
+            (checkpoint.film, checkpoint.cursor)
+        }
+        // Frames of a sequence that were never started have no checkpoint
+        | Err(error) if error.kind() == io::ErrorKind::NotFound && options.resume != paths.resume => {
+            (film::Film::new(scene.width(), scene.height()), scene::Cursor::default())
+        }
+        | Err(error) => {
+            eprintln!("[CHECKPOINT ERROR]: {}: {}", path.display(), error);
+            process::exit(1);
+        }
+    },
+    };
+
+    if coordinator.is_none() && !options.workers.is_empty() {
+        let connected = fs::read(&options.scene)
+            .map(|mut source| {
+                source.extend_from_slice(overrides(options, scene).as_bytes());
+                source
+            })
+            .and_then(|source| {
+                let path = options.scene.canonicalize()?;
+                remote::Coordinator::connect(&options.workers, &path, &source, scene)
+            });
+        match connected {
+        | Ok(connected) => *coordinator = Some(connected),
+        | Err(error) => {
+            eprintln!("[REMOTE ERROR]: {}", error);
+            process::exit(1);
+        }
+        }
+    }
+
+    let passes = scene.passes(&cursor, options.progressive);
+    let progress = if options.progress {
+        let offset = stats::PIXELS_RENDERED.read();
+        let pixels = scene.work(&passes);
+        Some(thread::spawn(move || progress::run(offset, pixels)))
+    } else {
+        None
+    };
+
+    let output: Box<dyn film::Sink + Sync> = match (&paths.merge, scene.crop()) {
+    | (Some(base), crop) => Box::new(image::Merge::new(
+        base,
+        &paths.output,
+        *scene.tone(),
+        crop.unwrap_or_else(|| film.bounds()),
+    )),
+    | (None, Some(crop)) => Box::new(film::Cropped::new(image::File::new(&paths.output, *scene.tone()), crop)),
+    | (None, None) => Box::new(image::File::new(&paths.output, *scene.tone())),
+    };
+    let hash = paths.save.as_ref().map(|_| scene.hash());
+    let seed = scene.seed();
+    let save = |film: &film::Film, cursor: &scene::Cursor| match (&paths.save, hash) {
+    | (Some(path), Some(hash)) => checkpoint::Checkpoint {
+        hash,
+        seed,
+        cursor: cursor.clone(),
+        film: film.clone(),
+    }.save(path),
+    | _ => Ok(()),
+    };
+
+    let scene = &*scene;
+    let interval = options.checkpoint.map(time::Duration::from_secs_f32);
+    let mut failure = None;
+    let checkpoint = |film: &film::Film, cursor: &scene::Cursor| {
+        if let Err(error) = output.write(film).and_then(|_| save(film, cursor)) {
+            failure.get_or_insert(error);
+        }
+    };
+    match coordinator {
+    | None => scene.render_passes(&mut film, &passes, interval, checkpoint),
+    | Some(coordinator) => {
+        let render = |film: &mut film::Film, cursor: &scene::Cursor, merged: &mut (dyn FnMut(&film::Film, &scene::Cursor) + Send)| {
+            coordinator.render_pass(scene, film, cursor, merged)
+        };
+        if let Err(error) = scene.render_passes_with(&mut film, &passes, interval, render, checkpoint) {
+            eprintln!("[REMOTE ERROR]: {}", error);
+            process::exit(1);
+        }
+    }
+    }
+    if let Some(error) = failure {
+        return Err(error.into())
+    }
 
-    #[cfg(feature = "stats")] {
-        println!("{}", photon::stats::ARENA_MEMORY);
-        println!("{}", photon::stats::INTERSECTION_TESTS);
-        println!("{}", photon::stats::BOUNDING_BOX_INTERSECTION_TESTS);
-        println!("{}", photon::stats::BVH_HITS);
-        println!("{}", photon::stats::BVH_MISSES);
-        println!("{}", photon::stats::SPHERE_INTERSECTION_TESTS);
-        println!("{}", photon::stats::TRI_INTERSECTION_TESTS);
-        println!("{}", photon::stats::LIST_INTERSECTION_TESTS);
+    let done = match passes.last() {
+    | Some(pass) => scene::Cursor { pass: pass.pass.clone(), tile: scene.tiles() },
+    | None => cursor,
+    };
+    save(&film, &done)?;
+    output.write(&film)?;
+    if let Some(heatmap) = &paths.heatmap {
+        let heatmap = image::Heatmap::new(heatmap, scene.samples());
+        match (&paths.merge, scene.crop()) {
+        | (None, Some(crop)) => film::Cropped::new(heatmap, crop).write(&film)?,
+        | _ => heatmap.write(&film)?,
+        }
+    }
+
+    if let Some(progress) = progress {
+        progress.join().unwrap().ok();
+    }
+
+    Ok(())
+}
+
+/// Parse a command-line override using the scene file grammar, exiting on failure.
+fn parse_spec<'scene, 'spec, T, F>(
+    arena: &'scene arena::Arena,
+    option: &str,
+    spec: &'spec str,
+    parse: F,
+) -> T where F: FnOnce(&mut scene::Parser<'scene, &'spec [u8]>) -> Result<T, scene::Error> {
+    let lexer = scene::Lexer::new(spec.as_bytes());
+    let mut parser = scene::Parser::new(arena, lexer);
+    match parse(&mut parser).and_then(|value| parser.parse_end().map(|_| value)) {
+    | Ok(value) => value,
+    | Err(error) => {
+        eprintln!("[CLI ERROR]: {}:{}", option, error);
+        process::exit(2);
+    }
+    }
+}
+
+/// Render tiles for every coordinator that connects, each on its own connection.
+fn serve(server: &cli::Server) -> Result<(), Box<dyn std::error::Error>> {
+    if let Some(threads) = server.threads {
+        rayon::ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()?;
+    }
+
+    let listener = net::TcpListener::bind(&server.address)?;
+    eprintln!("Listening on {}", listener.local_addr()?);
+
+    for stream in listener.incoming() {
+        let stream = match stream {
+        | Ok(stream) => stream,
+        | Err(error) => {
+            eprintln!("[SERVE ERROR]: {}", error);
+            continue;
+        }
+        };
+        thread::spawn(move || {
+            let peer = stream.peer_addr().map(|peer| peer.to_string()).unwrap_or_default();
+            if let Err(error) = remote::work(stream) {
+                eprintln!("[SERVE ERROR]: {}: {}", peer, error);
+            }
+        });
     }
 
-    #[cfg(feature = "progress")]
-    progress.join().unwrap().ok();
     Ok(())
 }
+
+/// Command-line overrides in scene file syntax, for workers to apply after the scene file.
+fn overrides(options: &cli::Options, scene: &scene::Scene) -> String {
+    let mut source = String::from("\n");
+    let specs = [
+        ("integrator", &options.integrator),
+        ("sampler", &options.sampler),
+        ("adaptive", &options.adaptive),
+        ("tiles", &options.tiles),
+        ("crop", &options.crop),
+        ("filter", &options.filter),
+        ("shutter", &options.shutter),
+    ];
+    for (keyword, spec) in &specs {
+        if let Some(spec) = spec {
+            writeln!(source, "{} {}", keyword, spec).expect("[INTERNAL ERROR]: formatting cannot fail");
+        }
+    }
+    if let Some(aspect) = scene.aspect() {
+        writeln!(source, "aspect {}", aspect).expect("[INTERNAL ERROR]: formatting cannot fail");
+    }
+    writeln!(
+        source,
+        "width {}\nheight {}\nsamples {}\nseed {}",
+        scene.width(),
+        scene.height(),
+        scene.samples(),
+        scene.seed(),
+    ).expect("[INTERNAL ERROR]: formatting cannot fail");
+    source
+}
@@ -2,13 +2,15 @@ mod axis;
 mod ray;
 mod vec;
 mod mat;
+mod quat;
 
 pub use axis::Axis;
 pub use ray::Ray;
 pub use vec::Vec3;
 pub use mat::Mat4;
+pub use quat::Quat;
 
-use rand::random;
+use crate::sample;
 
 pub const PI: f32 = std::f32::consts::PI;
 pub const FRAC_PI_2: f32 = std::f32::consts::FRAC_PI_2;
@@ -31,24 +33,31 @@ pub fn clamp(a: f32, lo: f32, hi: f32) -> f32 {
 }
 
 /// Generate a point in the unit sphere with uniform probability.
-pub fn uniform_sphere() -> Vec3 {
-    loop {
-        let p = Vec3::new(random(), random(), random()) * 2.0 - Vec3::new(1.0, 1.0, 1.0);
-        if p.len_sq() < 1.0 { break p }
-    }
+pub fn uniform_sphere<S: sample::Sampler>(sampler: &mut S) -> Vec3 {
+    let (u, v) = sampler.get_2d();
+    let r = sampler.get_1d().cbrt();
+    let z = 1.0 - 2.0 * u;
+    let s = max(0.0, 1.0 - z * z).sqrt();
+    Vec3::new(
+        r * s * (TAU * v).cos(),
+        r * s * (TAU * v).sin(),
+        r * z,
+    )
 }
 
 /// Generate a point in the unit X-Y disk with uniform probability.
-pub fn uniform_disk() -> Vec3 {
-    loop {
-        let p = Vec3::new(random(), random(), 0.0) * 2.0 - Vec3::new(1.0, 1.0, 0.0);
-        if p.len_sq() < 1.0 { break p }
-    }
+pub fn uniform_disk<S: sample::Sampler>(sampler: &mut S) -> Vec3 {
+    let (u, v) = sampler.get_2d();
+    let r = u.sqrt();
+    Vec3::new(
+        r * (TAU * v).cos(),
+        r * (TAU * v).sin(),
+        0.0,
+    )
 }
 
-pub fn cosine_sphere() -> Vec3 {
-    let u = random::<f32>();
-    let v = random::<f32>();
+pub fn cosine_sphere<S: sample::Sampler>(sampler: &mut S) -> Vec3 {
+    let (u, v) = sampler.get_2d();
     let r = u.sqrt();
     Vec3::new(
         r * (TAU * v).cos(),
@@ -1,9 +1,31 @@
+use std::ops::Mul;
+
 use crate::math;
 
+/// Row-major 4x4 matrix acting on column vectors.
 #[derive(Copy, Clone, Debug)]
 pub struct Mat4([f32; 16]);
 
+impl Default for Mat4 {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
 impl Mat4 {
+    pub fn new(m: [f32; 16]) -> Self {
+        Mat4(m)
+    }
+
+    pub fn identity() -> Self {
+        Mat4([
+            1.0, 0.0, 0.0, 0.0,
+            0.0, 1.0, 0.0, 0.0,
+            0.0, 0.0, 1.0, 0.0,
+            0.0, 0.0, 0.0, 1.0,
+        ])
+    }
+
     pub fn translate(v: math::Vec3) -> Self {
         Mat4([
             1.0, 0.0, 0.0, v.x(),
@@ -45,4 +67,200 @@ impl Mat4 {
     pub fn scale_z(s: f32) -> Self {
         Self::scale(math::Vec3::new(0.0, 0.0, s))
     }
+
+    /// Counter-clockwise rotation by `theta` radians about `axis`,
+    /// looking from the tip of the axis toward the origin.
+    pub fn rotate(axis: math::Vec3, theta: f32) -> Self {
+        let a = axis.normalize();
+        let (x, y, z) = (a.x(), a.y(), a.z());
+        let (sin, cos) = theta.sin_cos();
+        let k = 1.0 - cos;
+        Mat4([
+            x * x * k + cos,     x * y * k - z * sin, x * z * k + y * sin, 0.0,
+            y * x * k + z * sin, y * y * k + cos,     y * z * k - x * sin, 0.0,
+            z * x * k - y * sin, z * y * k + x * sin, z * z * k + cos,     0.0,
+            0.0,                 0.0,                 0.0,                 1.0,
+        ])
+    }
+
+    pub fn rotate_x(theta: f32) -> Self {
+        Self::rotate(math::Vec3::new(1.0, 0.0, 0.0), theta)
+    }
+
+    pub fn rotate_y(theta: f32) -> Self {
+        Self::rotate(math::Vec3::new(0.0, 1.0, 0.0), theta)
+    }
+
+    pub fn rotate_z(theta: f32) -> Self {
+        Self::rotate(math::Vec3::new(0.0, 0.0, 1.0), theta)
+    }
+
+    /// Maps a local frame looking down its negative Z axis, with Y up,
+    /// to one at `origin` looking toward `toward`, the same frame the camera uses.
+    pub fn look_at(origin: math::Vec3, toward: math::Vec3, up: math::Vec3) -> Self {
+        let w = (origin - toward).normalize();
+        let u = up.cross(&w).normalize();
+        let v = w.cross(&u);
+        Mat4([
+            u.x(), v.x(), w.x(), origin.x(),
+            u.y(), v.y(), w.y(), origin.y(),
+            u.z(), v.z(), w.z(), origin.z(),
+            0.0,   0.0,   0.0,   1.0,
+        ])
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f32 {
+        self.0[row * 4 + col]
+    }
+
+    pub fn transpose(&self) -> Self {
+        let m = &self.0;
+        Mat4([
+            m[0], m[4], m[8],  m[12],
+            m[1], m[5], m[9],  m[13],
+            m[2], m[6], m[10], m[14],
+            m[3], m[7], m[11], m[15],
+        ])
+    }
+
+    /// Inverse by cofactor expansion, or `None` if the matrix is singular.
+    pub fn inverse(&self) -> Option<Self> {
+        let m = &self.0;
+
+        let s0 = m[0] * m[5] - m[4] * m[1];
+        let s1 = m[0] * m[6] - m[4] * m[2];
+        let s2 = m[0] * m[7] - m[4] * m[3];
+        let s3 = m[1] * m[6] - m[5] * m[2];
+        let s4 = m[1] * m[7] - m[5] * m[3];
+        let s5 = m[2] * m[7] - m[6] * m[3];
+
+        let c5 = m[10] * m[15] - m[14] * m[11];
+        let c4 = m[9] * m[15] - m[13] * m[11];
+        let c3 = m[9] * m[14] - m[13] * m[10];
+        let c2 = m[8] * m[15] - m[12] * m[11];
+        let c1 = m[8] * m[14] - m[12] * m[10];
+        let c0 = m[8] * m[13] - m[12] * m[9];
+
+        let det = s0 * c5 - s1 * c4 + s2 * c3 + s3 * c2 - s4 * c1 + s5 * c0;
+        if det.abs() < f32::MIN_POSITIVE || !det.is_finite() {
+            return None
+        }
+        let inv = 1.0 / det;
+
+        Some(Mat4([
+            ( m[5] * c5 - m[6] * c4 + m[7] * c3) * inv,
+            (-m[1] * c5 + m[2] * c4 - m[3] * c3) * inv,
+            ( m[13] * s5 - m[14] * s4 + m[15] * s3) * inv,
+            (-m[9] * s5 + m[10] * s4 - m[11] * s3) * inv,
+
+            (-m[4] * c5 + m[6] * c2 - m[7] * c1) * inv,
+            ( m[0] * c5 - m[2] * c2 + m[3] * c1) * inv,
+            (-m[12] * s5 + m[14] * s2 - m[15] * s1) * inv,
+            ( m[8] * s5 - m[10] * s2 + m[11] * s1) * inv,
+
+            ( m[4] * c4 - m[5] * c2 + m[7] * c0) * inv,
+            (-m[0] * c4 + m[1] * c2 - m[3] * c0) * inv,
+            ( m[12] * s4 - m[13] * s2 + m[15] * s0) * inv,
+            (-m[8] * s4 + m[9] * s2 - m[11] * s0) * inv,
+
+            (-m[4] * c3 + m[5] * c1 - m[6] * c0) * inv,
+            ( m[0] * c3 - m[1] * c1 + m[2] * c0) * inv,
+            (-m[12] * s3 + m[13] * s1 - m[14] * s0) * inv,
+            ( m[8] * s3 - m[9] * s1 + m[10] * s0) * inv,
+        ]))
+    }
+
+    /// Transforms `p` as a point, applying translation.
+    pub fn point(&self, p: &math::Vec3) -> math::Vec3 {
+        let m = &self.0;
+        let (x, y, z) = (p.x(), p.y(), p.z());
+        let w = m[12] * x + m[13] * y + m[14] * z + m[15];
+        math::Vec3::new(
+            m[0] * x + m[1] * y + m[2] * z + m[3],
+            m[4] * x + m[5] * y + m[6] * z + m[7],
+            m[8] * x + m[9] * y + m[10] * z + m[11],
+        ) / w
+    }
+
+    /// Transforms `v` as a direction, ignoring translation.
+    pub fn vector(&self, v: &math::Vec3) -> math::Vec3 {
+        let m = &self.0;
+        let (x, y, z) = (v.x(), v.y(), v.z());
+        math::Vec3::new(
+            m[0] * x + m[1] * y + m[2] * z,
+            m[4] * x + m[5] * y + m[6] * z,
+            m[8] * x + m[9] * y + m[10] * z,
+        )
+    }
+
+    /// Transforms `n` as a surface normal of geometry transformed by the
+    /// inverse of this matrix, which is multiplication by its transpose.
+    ///
+    /// The result is not normalized.
+    pub fn normal(&self, n: &math::Vec3) -> math::Vec3 {
+        let m = &self.0;
+        let (x, y, z) = (n.x(), n.y(), n.z());
+        math::Vec3::new(
+            m[0] * x + m[4] * y + m[8] * z,
+            m[1] * x + m[5] * y + m[9] * z,
+            m[2] * x + m[6] * y + m[10] * z,
+        )
+    }
+}
+
+/// Composition: `(a * b)` applies `b` first, then `a`.
+impl Mul for Mat4 {
+    type Output = Mat4;
+    fn mul(self, rhs: Mat4) -> Mat4 {
+        let (a, b) = (&self.0, &rhs.0);
+        let mut m = [0.0; 16];
+        for row in 0..4 {
+            for col in 0..4 {
+                m[row * 4 + col] = (0..4)
+                    .map(|k| a[row * 4 + k] * b[k * 4 + col])
+                    .sum();
+            }
+        }
+        Mat4(m)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(lhs: &Mat4, rhs: &Mat4) {
+        for (l, r) in lhs.0.iter().zip(&rhs.0) {
+            assert!((l - r).abs() < 1e-5, "{:?} != {:?}", lhs, rhs);
+        }
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let m = Mat4::translate(math::Vec3::new(1.0, -2.0, 3.0))
+            * Mat4::rotate(math::Vec3::new(1.0, 1.0, 0.0), 0.7)
+            * Mat4::scale(math::Vec3::new(2.0, 3.0, 0.5));
+        let inverse = m.inverse().unwrap();
+        assert_close(&(m * inverse), &Mat4::identity());
+        assert_close(&(inverse * m), &Mat4::identity());
+    }
+
+    #[test]
+    fn singular_has_no_inverse() {
+        assert!(Mat4::scale(math::Vec3::new(1.0, 0.0, 1.0)).inverse().is_none());
+        assert!(Mat4::new([1.0; 16]).inverse().is_none());
+    }
+
+    #[test]
+    fn rotate_is_counter_clockwise() {
+        let p = Mat4::rotate_z(math::FRAC_PI_2).point(&math::Vec3::new(1.0, 0.0, 0.0));
+        assert!((p - math::Vec3::new(0.0, 1.0, 0.0)).len() < 1e-6);
+    }
+
+    #[test]
+    fn scale_then_translate() {
+        let m = Mat4::translate(math::Vec3::new(1.0, 0.0, 0.0)) * Mat4::scale(math::Vec3::broadcast(2.0));
+        let p = m.point(&math::Vec3::new(1.0, 1.0, 1.0));
+        assert_eq!((p.x(), p.y(), p.z()), (3.0, 2.0, 2.0));
+    }
 }
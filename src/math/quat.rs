@@ -0,0 +1,132 @@
+use std::ops::Mul;
+
+use crate::math;
+
+/// Unit quaternion representing a rotation.
+#[derive(Copy, Clone, Debug)]
+pub struct Quat {
+    v: math::Vec3,
+    w: f32,
+}
+
+impl Default for Quat {
+    fn default() -> Self {
+        Self::identity()
+    }
+}
+
+impl Quat {
+    pub fn identity() -> Self {
+        Quat { v: math::Vec3::new(0.0, 0.0, 0.0), w: 1.0 }
+    }
+
+    /// Counter-clockwise rotation by `theta` radians about `axis`, as in `Mat4::rotate`.
+    pub fn rotate(axis: math::Vec3, theta: f32) -> Self {
+        let (sin, cos) = (theta / 2.0).sin_cos();
+        Quat { v: axis.normalize() * sin, w: cos }
+    }
+
+    fn dot(&self, rhs: &Self) -> f32 {
+        self.v.dot(&rhs.v) + self.w * rhs.w
+    }
+
+    fn normalize(&self) -> Self {
+        let len = self.dot(self).sqrt();
+        Quat { v: self.v / len, w: self.w / len }
+    }
+
+    /// Blend between `self` at `t = 0.0` and `other` at `t = 1.0`
+    /// at constant angular speed, along the shorter arc.
+    pub fn slerp(&self, other: &Self, t: f32) -> Self {
+        let (other, cos) = match self.dot(other) {
+        | cos if cos < 0.0 => (Quat { v: -other.v, w: -other.w }, -cos),
+        | cos => (*other, cos),
+        };
+
+        // Nearly parallel, where the sine below vanishes
+        if cos > 0.9995 {
+            return Quat {
+                v: self.v.lerp(&other.v, t),
+                w: self.w + (other.w - self.w) * t,
+            }.normalize()
+        }
+
+        let theta = cos.acos();
+        let sin = theta.sin();
+        let a = ((1.0 - t) * theta).sin() / sin;
+        let b = (t * theta).sin() / sin;
+        Quat { v: self.v * a + other.v * b, w: self.w * a + other.w * b }
+    }
+
+    pub fn matrix(&self) -> math::Mat4 {
+        let (x, y, z, w) = (self.v.x(), self.v.y(), self.v.z(), self.w);
+        math::Mat4::new([
+            1.0 - 2.0 * (y * y + z * z), 2.0 * (x * y - w * z),       2.0 * (x * z + w * y),       0.0,
+            2.0 * (x * y + w * z),       1.0 - 2.0 * (x * x + z * z), 2.0 * (y * z - w * x),       0.0,
+            2.0 * (x * z - w * y),       2.0 * (y * z + w * x),       1.0 - 2.0 * (x * x + y * y), 0.0,
+            0.0,                         0.0,                         0.0,                         1.0,
+        ])
+    }
+}
+
+/// Composition: `(a * b)` applies `b` first, then `a`.
+impl Mul for Quat {
+    type Output = Quat;
+    fn mul(self, rhs: Quat) -> Quat {
+        Quat {
+            v: rhs.v * self.w + self.v * rhs.w + self.v.cross(&rhs.v),
+            w: self.w * rhs.w - self.v.dot(&rhs.v),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(lhs: &Quat, rhs: &Quat) {
+        // Negated quaternions represent the same rotation
+        let sign = if lhs.dot(rhs) < 0.0 { -1.0 } else { 1.0 };
+        assert!((lhs.v - rhs.v * sign).len() < 1e-5, "{:?} != {:?}", lhs, rhs);
+        assert!((lhs.w - rhs.w * sign).abs() < 1e-5, "{:?} != {:?}", lhs, rhs);
+    }
+
+    fn z(theta: f32) -> Quat {
+        Quat::rotate(math::Vec3::new(0.0, 0.0, 1.0), theta)
+    }
+
+    #[test]
+    fn matrix_matches_rotate() {
+        let axis = math::Vec3::new(1.0, 2.0, 3.0);
+        let lhs = Quat::rotate(axis, 1.2).matrix();
+        let rhs = math::Mat4::rotate(axis, 1.2);
+        for row in 0..4 {
+            for col in 0..4 {
+                assert!((lhs.get(row, col) - rhs.get(row, col)).abs() < 1e-5);
+            }
+        }
+    }
+
+    #[test]
+    fn slerp_hits_endpoints() {
+        let (a, b) = (z(0.3), z(1.5));
+        assert_close(&a.slerp(&b, 0.0), &a);
+        assert_close(&a.slerp(&b, 1.0), &b);
+    }
+
+    #[test]
+    fn slerp_midpoint_halves_angle() {
+        assert_close(&Quat::identity().slerp(&z(math::FRAC_PI_2), 0.5), &z(math::FRAC_PI_2 / 2.0));
+    }
+
+    #[test]
+    fn slerp_takes_shorter_arc() {
+        let three_quarters = z(3.0 * math::FRAC_PI_2);
+        assert_close(&Quat::identity().slerp(&three_quarters, 0.5), &z(-math::FRAC_PI_2 / 2.0));
+    }
+
+    #[test]
+    fn compose_applies_right_first() {
+        assert_close(&(z(0.5) * z(0.25)), &z(0.75));
+    }
+}
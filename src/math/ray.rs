@@ -9,6 +9,9 @@ pub struct Ray {
     pub max: f32,
     pub inv: Vec3,
     pub sign: [usize; 3],
+
+    /// Instant at which the ray is cast, in frames
+    pub time: f32,
 }
 
 impl Ray {
@@ -25,9 +28,19 @@ impl Ray {
                 (dir.y() < 0.0) as usize,
                 (dir.z() < 0.0) as usize,
             ],
+            time: 0.0,
         }
     }
 
+    /// Ray from `origin` toward `dir` cast at the same instant as this one.
+    pub fn spawn(&self, origin: Vec3, dir: Vec3) -> Self {
+        Ray::new(origin, dir).with_time(self.time)
+    }
+
+    pub fn with_time(&self, time: f32) -> Self {
+        Ray { time, .. *self }
+    }
+
     pub fn with_origin(&self, origin: Vec3) -> Self {
         Ray { p: origin, .. *self }
     }
@@ -36,6 +49,10 @@ impl Ray {
         self.p + self.d * t
     }
 
+    pub fn set_min(&mut self, t: f32) {
+        self.min = t
+    }
+
     pub fn set_max(&mut self, t: f32) {
         self.max = t
     }
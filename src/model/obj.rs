@@ -1,30 +1,30 @@
+use std::io;
+
 use crate::arena;
-use crate::bxdf;
 use crate::math::Vec3;
 use crate::geom;
 
 pub fn parse<'scene, P>(
     obj: P,
     arena: &'scene arena::Arena,
-    material: &'scene bxdf::Any<'scene>,
-) -> geom::Mesh<'scene>
+) -> io::Result<geom::Prototype<'scene>>
     where P: AsRef<std::path::Path>,
 {
-    let obj = std::fs::read_to_string(obj).expect("[INTERNAL ERROR]: could not read OBJ file");
+    let obj = std::fs::read_to_string(obj)?;
     let mut fs = Vec::new();
     let mut vs = Vec::new();
     let mut ns = Vec::new();
 
-    for line in obj.lines() {
+    for (number, line) in obj.lines().enumerate() {
 
         let mut iter = line.trim_end()
             .split_whitespace();
 
         macro_rules! go {
             ($type:ty) => {
-                match iter.next() {
-                | Some(s) => s.parse::<$type>().expect("[INTERNAL ERROR]: invalid OBJ file"),
-                | None => panic!("[INTERNAL ERROR]: invalid OBJ file"),
+                match iter.next().map(str::parse::<$type>) {
+                | Some(Ok(value)) => value,
+                | _ => return Err(invalid(number, "malformed number")),
                 }
             }
         }
@@ -37,7 +37,11 @@ pub fn parse<'scene, P>(
             ns.push(Vec3::default());
         }
         | Some("f") => {
-            fs.push((go!(usize) - 1, go!(usize) - 1, go!(usize) - 1));
+            let (a, b, c) = (go!(usize), go!(usize), go!(usize));
+            if [a, b, c].iter().any(|&index| index == 0 || index > vs.len()) {
+                return Err(invalid(number, "face refers to an undefined vertex"))
+            }
+            fs.push((a - 1, b - 1, c - 1));
         }
         | _ => continue,
         }
@@ -61,5 +65,10 @@ pub fn parse<'scene, P>(
         })
         .collect::<Vec<_>>();
 
-    geom::Mesh::new(arena, material, &ts)
+    Ok(geom::Prototype::new(arena, &ts))
+}
+
+/// Error for malformed input on the zero-indexed line `number`.
+fn invalid(number: usize, message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, format!("invalid OBJ file: line {}: {}", number + 1, message))
 }
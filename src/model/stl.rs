@@ -1,3 +1,4 @@
+use std::io;
 use std::path;
 use std::str;
 use std::fs;
@@ -6,30 +7,28 @@ use byteorder::ByteOrder;
 use byteorder::LE;
 
 use crate::arena;
-use crate::bxdf;
 use crate::geom;
 use crate::math;
 
 pub fn parse<'scene, P>(
     stl: P,
     arena: &'scene arena::Arena,
-    material: &'scene bxdf::Any<'scene>,
-) -> geom::Mesh<'scene>
+) -> io::Result<geom::Prototype<'scene>>
     where P: AsRef<path::Path>
 {
-    let stl = fs::read(stl).expect("[INTERNAL ERROR]: could not read STL file");
-    if &stl[0..5] == "solid".as_bytes() {
+    let stl = fs::read(stl)?;
+    if stl.starts_with(b"solid") {
         str::from_utf8(&stl)
             .map(ASCII::new)
-            .expect("[STL ERROR]: invalid ASCII STL file")
-            .parse(arena, material)
+            .map_err(|_| invalid("ASCII STL file is not UTF-8"))?
+            .parse(arena)
     } else {
         Binary::new(stl)
-            .parse(arena, material)
+            .parse(arena)
     }
 }
 
-/// ASCII STL parser. Panics on malformed input.
+/// ASCII STL parser.
 struct ASCII<'str>(str::SplitWhitespace<'str>);
 
 impl<'str> ASCII<'str> {
@@ -40,50 +39,52 @@ impl<'str> ASCII<'str> {
     fn parse<'scene>(
         mut self,
         arena: &'scene arena::Arena,
-        material: &'scene bxdf::Any<'scene>
-    ) -> geom::Mesh<'scene> {
+    ) -> io::Result<geom::Prototype<'scene>> {
         let mut ts = Vec::new();
         while let Some(token) = self.0.next() {
             if token != "facet" { continue }
-            ts.push(self.parse_tri(arena));
+            ts.push(self.parse_tri(arena)?);
         }
-        geom::Mesh::new(arena, material, &ts)
+        Ok(geom::Prototype::new(arena, &ts))
     }
 
-    fn parse_tri<'scene>(&mut self, arena: &'scene arena::Arena) -> geom::Tri<'scene> {
-        self.verify("normal");
-        let n = arena.alloc(self.parse_vec3());
-        self.verify("outer");
-        self.verify("loop");
-        self.verify("vertex");
-        let a = arena.alloc(self.parse_vec3());
-        self.verify("vertex");
-        let b = arena.alloc(self.parse_vec3());
-        self.verify("vertex");
-        let c = arena.alloc(self.parse_vec3());
-        self.verify("endloop");
-        geom::Tri::new([a, b, c], [n, n, n])
+    fn parse_tri<'scene>(&mut self, arena: &'scene arena::Arena) -> io::Result<geom::Tri<'scene>> {
+        self.verify("normal")?;
+        let n = arena.alloc(self.parse_vec3()?);
+        self.verify("outer")?;
+        self.verify("loop")?;
+        self.verify("vertex")?;
+        let a = arena.alloc(self.parse_vec3()?);
+        self.verify("vertex")?;
+        let b = arena.alloc(self.parse_vec3()?);
+        self.verify("vertex")?;
+        let c = arena.alloc(self.parse_vec3()?);
+        self.verify("endloop")?;
+        Ok(geom::Tri::new([a, b, c], [n, n, n]))
     }
 
-    fn parse_vec3(&mut self) -> math::Vec3 {
-        let x = self.parse_f32(); 
-        let y = self.parse_f32(); 
-        let z = self.parse_f32(); 
-        math::Vec3::new(x, y, z)
+    fn parse_vec3(&mut self) -> io::Result<math::Vec3> {
+        let x = self.parse_f32()?;
+        let y = self.parse_f32()?;
+        let z = self.parse_f32()?;
+        Ok(math::Vec3::new(x, y, z))
     }
 
-    fn parse_f32(&mut self) -> f32 {
+    fn parse_f32(&mut self) -> io::Result<f32> {
         self.0.next()
             .and_then(|s| s.parse::<f32>().ok())
-            .expect("[STL ERROR]: invalid STL file")
+            .ok_or_else(|| invalid("expected number in ASCII STL file"))
     }
 
-    fn verify(&mut self, tag: &'static str) {
-        assert!(self.0.next() == Some(tag))
+    fn verify(&mut self, tag: &'static str) -> io::Result<()> {
+        match self.0.next() {
+        | Some(token) if token == tag => Ok(()),
+        | _ => Err(invalid(&format!("expected '{}' in ASCII STL file", tag))),
+        }
     }
 }
 
-/// Binary STL parser. Panics on malformed input.
+/// Binary STL parser.
 struct Binary {
     buffer: Vec<u8>,
     cursor: usize,
@@ -117,11 +118,16 @@ impl Binary {
     fn parse<'scene>(
         mut self,
         arena: &'scene arena::Arena,
-        material: &'scene bxdf::Any<'scene>
-    ) -> geom::Mesh<'scene> {
+    ) -> io::Result<geom::Prototype<'scene>> {
+        if self.buffer.len() < 84 {
+            return Err(invalid("binary STL file is missing its header"))
+        }
 
         let mut ts = Vec::new();
         let count = self.parse_u32();
+        if self.buffer.len() < 84 + 50 * count as usize {
+            return Err(invalid("binary STL file is shorter than its triangle count"))
+        }
         for _ in 0..count {
             let n = arena.alloc(self.parse_vec3());
             let a = arena.alloc(self.parse_vec3());
@@ -131,6 +137,10 @@ impl Binary {
             self.cursor += 2;
         }
 
-        geom::Mesh::new(arena, material, &ts)
+        Ok(geom::Prototype::new(arena, &ts))
     }
 }
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
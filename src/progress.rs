@@ -1,15 +1,15 @@
 use std::io::Write;
+use std::sync::Mutex;
 
 use crate::stats::PIXELS_RENDERED;
 
 const BARS: usize = 50;
 const DONE: &'static str = "█";
 const REST: &'static str = "░";
+const ACTIVE: &str = "▒";
 
 const HIDE: &'static str = "\x1B[?25l";
 const SHOW: &'static str = "\x1B[?25h";
-const SAVE: &'static str = "\x1B[s";
-const RESTORE: &'static str = "\x1B[u";
 
 #[derive(Copy, Clone)]
 enum Spinner {
@@ -39,46 +39,128 @@ impl std::fmt::Display for Spinner {
     }
 }
 
-pub fn run(total: usize) -> Result<(), std::io::Error> {
+/// Rendering state of a single tile.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum State {
+    Pending,
+    Active,
+    Done,
+}
+
+/// Rendering state of every tile in the current pass, for display.
+#[derive(Debug)]
+pub struct Board {
+    tiles: Mutex<(usize, Vec<State>)>,
+}
+
+/// Tile states of the render in progress.
+pub static BOARD: Board = Board { tiles: Mutex::new((0, Vec::new())) };
+
+impl Board {
+    /// Start a new pass over a grid of `columns x rows` pending tiles.
+    pub fn reset(&self, columns: usize, rows: usize) {
+        *self.lock() = (columns, vec![State::Pending; columns * rows]);
+    }
+
+    pub fn set(&self, column: usize, row: usize, state: State) {
+        let mut tiles = self.lock();
+        let columns = tiles.0;
+        if let Some(tile) = tiles.1.get_mut(row * columns + column) {
+            *tile = state;
+        }
+    }
+
+    /// Render the board as lines of text at most `width` characters wide.
+    fn draw(&self, width: usize) -> Vec<String> {
+        let (columns, tiles) = self.lock().clone();
+        if columns == 0 || tiles.is_empty() { return Vec::new() }
+        let rows = tiles.len() / columns;
+
+        // Terminal cells are roughly twice as tall as they are wide
+        let scale = columns.div_ceil(width);
+        let (sx, sy) = (scale, scale * 2);
+
+        (0..rows.div_ceil(sy))
+            .map(|y| {
+                (0..columns.div_ceil(sx))
+                    .map(|x| {
+                        let group = (y * sy..((y + 1) * sy).min(rows))
+                            .flat_map(|row| (x * sx..((x + 1) * sx).min(columns)).map(move |column| (column, row)))
+                            .map(|(column, row)| tiles[row * columns + column]);
+                        let mut done = true;
+                        for state in group {
+                            match state {
+                            | State::Active => return ACTIVE,
+                            | State::Pending => done = false,
+                            | State::Done => (),
+                            }
+                        }
+                        if done { DONE } else { REST }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, (usize, Vec<State>)> {
+        self.tiles.lock().expect("[INTERNAL ERROR]: tile board lock poisoned")
+    }
+}
+
+/// Draw a progress bar until `total` more pixels than `offset` have been rendered.
+pub fn run(offset: usize, total: usize) -> Result<(), std::io::Error> {
     let start = std::time::Instant::now();
     let stdout = std::io::stdout();
     let mut out = stdout.lock();
     let mut spinner = Spinner::A;
     let mut rendered = 0;
+    let mut lines = 0;
 
-    write!(out, "{}{}", HIDE, SAVE)?;
+    write!(out, "{}", HIDE)?;
 
-    while rendered < total {
+    loop {
         spinner.rotate();
-        rendered = PIXELS_RENDERED.read();
+        rendered = rendered.max(PIXELS_RENDERED.read() - offset);
 
         let now = std::time::Instant::now();
         let mut span = now.duration_since(start).as_secs();
         let h = span / 3600; span %= 3600;
         let m = span / 60;   span %= 60;
         let s = span;
-        let done = rendered * BARS / total;
+        let done = rendered.min(total) * BARS / total.max(1);
         let rest = BARS - done;
 
-        write!(
+        // Return to the top of the previous frame
+        if lines > 0 {
+            write!(out, "\x1B[{}F", lines)?;
+        }
+
+        writeln!(
             out,
-            "{}[{}] | Elapsed: {:0>2}:{:0>2}:{:0>2} | [{}{}] | {:.2}% | {} out of {} pixels",
-            RESTORE,
+            "\r[{}] | Elapsed: {:0>2}:{:0>2}:{:0>2} | [{}{}] | {:.2}% | {} out of {} pixels",
             spinner,
             h, m, s,
             DONE.repeat(done),
             REST.repeat(rest),
-            100.0 * rendered as f32 / total as f32,
+            100.0 * rendered as f32 / total.max(1) as f32,
             rendered,
             total,
         )?;
 
+        let map = BOARD.draw(BARS);
+        for line in &map {
+            writeln!(out, " {}", line)?;
+        }
+        lines = map.len() + 1;
+
+        if rendered >= total { break }
+
         std::thread::sleep(
             std::time::Duration::from_millis(10)
         );
     }
 
-    writeln!(out, "{}", SHOW)?;
+    write!(out, "{}", SHOW)?;
 
     Ok(())
 }
@@ -0,0 +1,488 @@
+use std::collections::VecDeque;
+use std::io;
+use std::io::Read;
+use std::io::Write;
+use std::net;
+use std::ops;
+use std::path;
+use std::sync;
+use std::sync::mpsc;
+use std::thread;
+
+use byteorder::ReadBytesExt;
+use byteorder::WriteBytesExt;
+use byteorder::LE;
+use rayon::prelude::*;
+
+use crate::adaptive;
+use crate::arena;
+use crate::checkpoint;
+use crate::integrator;
+use crate::film;
+use crate::math;
+use crate::progress;
+use crate::scene;
+use crate::schedule;
+use crate::stats;
+
+const MAGIC: &[u8; 8] = b"PHOTONRD";
+const VERSION: u32 = 3;
+
+/// Address that `photon serve` listens on by default.
+pub const ADDRESS: &str = "127.0.0.1:7700";
+
+// Coordinator to worker
+const RENDER: u8 = 0;
+const DONE: u8 = 1;
+
+// Worker to coordinator
+const READY: u8 = 0;
+const FAILED: u8 = 1;
+
+/// Tiles kept in flight per worker thread, to hide network latency.
+const QUEUE: usize = 2;
+
+/// Serve a single coordinator: load the scene it sends, then render
+/// the tiles it asks for until it hangs up.
+///
+/// The scene is parsed as if read from the coordinator's path, so it must refer
+/// to the same includes and models on this machine. Its hash is checked against
+/// the coordinator's to make sure both sides take the same samples.
+pub fn work(stream: net::TcpStream) -> io::Result<()> {
+    stream.set_nodelay(true)?;
+    let mut input = io::BufReader::new(stream.try_clone()?);
+    let mut out = io::BufWriter::new(stream);
+
+    out.write_all(MAGIC)?;
+    out.write_u32::<LE>(VERSION)?;
+    out.write_u64::<LE>(rayon::current_num_threads() as u64)?;
+    out.flush()?;
+
+    let path = path::PathBuf::from(read_string(&mut input)?);
+    let source = read_bytes(&mut input)?;
+    let shot = input.read_u64::<LE>()? as usize;
+    let frame = input.read_u64::<LE>()? as usize;
+    let hash = input.read_u64::<LE>()?;
+
+    let arena = arena::Arena::default();
+    let lexer = scene::Lexer::new(source.as_slice());
+    let scene = match scene::Parser::new(&arena, lexer).with_path(&path).parse_scene() {
+    | Ok(mut scene) if shot < scene.shots().len() => {
+        scene.select(shot, frame);
+        scene
+    }
+    | Ok(scene) => scene,
+    | Err(error) => return Err(refuse(&mut out, &error.to_string())),
+    };
+    if scene.hash() != hash {
+        return Err(refuse(&mut out, "scene differs from the coordinator's"))
+    }
+    if let integrator::Any::Heatmap(_) = scene.integrator() {
+        stats::enable();
+    }
+    out.write_u8(READY)?;
+    out.flush()?;
+
+    let regions = scene.schedule().split(&scene.bounds());
+    let scene = &scene;
+    let out = sync::Mutex::new(out);
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        let reader = scope.spawn(move || -> io::Result<()> {
+            loop {
+                match input.read_u8()? {
+                | RENDER => (),
+                | DONE => return Ok(()),
+                | _ => return Err(checkpoint::invalid("unexpected message from coordinator")),
+                }
+                let index = input.read_u64::<LE>()? as usize;
+                let shot = input.read_u64::<LE>()? as usize;
+                let frame = input.read_u64::<LE>()? as usize;
+                let start = input.read_u64::<LE>()? as usize;
+                let end = input.read_u64::<LE>()? as usize;
+                let count = input.read_u64::<LE>()? as usize;
+                let region = regions
+                    .get(index)
+                    .cloned()
+                    .ok_or_else(|| checkpoint::invalid("tile index out of range"))?;
+                if shot >= scene.shots().len() {
+                    return Err(checkpoint::invalid("shot index out of range"))
+                }
+                if count != 0 && count != region.width * region.height {
+                    return Err(checkpoint::invalid("sample statistics do not match tile"))
+                }
+                let previous = (0..count)
+                    .map(|_| checkpoint::read_estimate(&mut input))
+                    .collect::<io::Result<Vec<_>>>()?;
+                scene.trace_photons(frame, start..end);
+                if sender.send((index, shot, frame, region, start..end, previous)).is_err() {
+                    return Ok(())
+                }
+            }
+        });
+
+        let failure = sync::Mutex::new(None);
+        receiver.into_iter()
+            .par_bridge()
+            .for_each(|(index, shot, frame, region, pass, previous): Request| {
+                let tile = scene.render_tile(shot, frame, &region, pass, |x, y| {
+                    previous
+                        .get((y - region.y) * region.width + x - region.x)
+                        .copied()
+                        .unwrap_or_default()
+                });
+                let mut out = out.lock().expect("[INTERNAL ERROR]: connection lock poisoned");
+                if let Err(error) = write_tile(&mut *out, index, &tile) {
+                    failure.lock()
+                        .expect("[INTERNAL ERROR]: failure lock poisoned")
+                        .get_or_insert(error);
+                }
+            });
+
+        reader.join().expect("[INTERNAL ERROR]: request reader panicked")?;
+        match failure.into_inner().expect("[INTERNAL ERROR]: failure lock poisoned") {
+        | Some(error) => Err(error),
+        | None => Ok(()),
+        }
+    })
+}
+
+/// Tile index, shot, frame, tile, sample range, and earlier sample statistics of a tile to render.
+type Request = (usize, usize, usize, schedule::Region, ops::Range<usize>, Vec<adaptive::Estimate>);
+
+/// Connection to a `photon serve` worker that has loaded the scene.
+#[derive(Debug)]
+struct Worker {
+    address: String,
+    input: io::BufReader<net::TcpStream>,
+    out: io::BufWriter<net::TcpStream>,
+    threads: usize,
+}
+
+impl Worker {
+    fn connect(
+        address: &str,
+        path: &path::Path,
+        source: &[u8],
+        scene: &scene::Scene,
+        hash: u64,
+    ) -> io::Result<Self> {
+        let stream = net::TcpStream::connect(address)?;
+        stream.set_nodelay(true)?;
+        let mut input = io::BufReader::new(stream.try_clone()?);
+        let mut out = io::BufWriter::new(stream);
+
+        let mut magic = [0; 8];
+        input.read_exact(&mut magic)?;
+        if &magic != MAGIC {
+            return Err(checkpoint::invalid("not a photon worker"))
+        }
+        if input.read_u32::<LE>()? != VERSION {
+            return Err(checkpoint::invalid("unsupported worker version"))
+        }
+        let threads = input.read_u64::<LE>()? as usize;
+
+        write_bytes(&mut out, path.to_string_lossy().as_bytes())?;
+        write_bytes(&mut out, source)?;
+        out.write_u64::<LE>(scene.shot() as u64)?;
+        out.write_u64::<LE>(scene.frame() as u64)?;
+        out.write_u64::<LE>(hash)?;
+        out.flush()?;
+
+        let status = input.read_u8().map_err(|error| match error.kind() {
+        | io::ErrorKind::UnexpectedEof => io::Error::other("worker hung up while loading the scene"),
+        | _ => error,
+        })?;
+        match status {
+        | READY => Ok(Worker { address: address.to_owned(), input, out, threads: threads.max(1) }),
+        | FAILED => Err(io::Error::other(read_string(&mut input)?)),
+        | _ => Err(checkpoint::invalid("unexpected message from worker")),
+        }
+    }
+}
+
+impl Drop for Worker {
+    fn drop(&mut self) {
+        self.out.write_u8(DONE)
+            .and_then(|_| self.out.flush())
+            .ok();
+    }
+}
+
+/// Tiles of the current pass that no worker is rendering yet.
+#[derive(Debug)]
+struct Queue {
+    pending: VecDeque<usize>,
+    merged: usize,
+}
+
+/// Renders passes by handing their tiles out to `photon serve` workers.
+///
+/// Tiles are merged in the same fixed order as a local render,
+/// so the image does not depend on which worker rendered what.
+#[derive(Debug)]
+pub struct Coordinator {
+    workers: Vec<Worker>,
+    seed: u64,
+}
+
+impl Coordinator {
+    /// Connect to the worker at each of `addresses` and have it load `scene`,
+    /// whose description `source` was read from `path`.
+    ///
+    /// Workers follow the coordinator's choice of shot and frame, but keep the seed
+    /// that `scene` has now: passes rendered with a different seed will fail.
+    pub fn connect<A: AsRef<str>>(
+        addresses: &[A],
+        path: &path::Path,
+        source: &[u8],
+        scene: &scene::Scene,
+    ) -> io::Result<Self> {
+        let hash = scene.hash();
+        let workers = addresses
+            .iter()
+            .map(AsRef::as_ref)
+            .map(|address| {
+                Worker::connect(address, path, source, scene, hash)
+                    .map_err(|error| io::Error::new(error.kind(), format!("{}: {}", address, error)))
+            })
+            .collect::<io::Result<Vec<_>>>()?;
+        Ok(Coordinator { workers, seed: scene.seed() })
+    }
+
+    /// Total number of render threads across all workers.
+    pub fn threads(&self) -> usize {
+        self.workers.iter().map(|worker| worker.threads).sum()
+    }
+
+    /// Remote equivalent of [`Scene::render_pass`](scene::Scene::render_pass).
+    ///
+    /// Tiles held by a worker that fails are handed to the others, and the
+    /// failed worker is dropped. Fails only if every worker does.
+    pub fn render_pass<F>(
+        &mut self,
+        scene: &scene::Scene,
+        film: &mut film::Film,
+        cursor: &scene::Cursor,
+        merged: F,
+    ) -> io::Result<()> where F: FnMut(&film::Film, &scene::Cursor) + Send {
+        if scene.seed() != self.seed {
+            return Err(io::Error::other("workers were started with a different seed"))
+        }
+
+        let previous = match scene.adaptive() {
+        | Some(_) => film.pixels().iter().map(film::Pixel::estimate).collect(),
+        | None => Vec::new(),
+        };
+
+        let bounds = scene.bounds();
+        let (columns, rows) = scene.schedule().grid(&bounds);
+        let regions = scene.schedule().split(&bounds);
+        progress::BOARD.reset(columns, rows);
+        for region in &regions[..cursor.tile.min(regions.len())] {
+            progress::BOARD.set(region.column, region.row, progress::State::Done);
+        }
+
+        let total = regions.len().saturating_sub(cursor.tile);
+        let queue = sync::Mutex::new(Queue { pending: (cursor.tile..regions.len()).collect(), merged: 0 });
+        let changed = sync::Condvar::new();
+        let film = sync::Mutex::new((film::Ordered::new(film, cursor.tile), merged));
+
+        let results = thread::scope(|scope| {
+            let handles = self.workers
+                .iter_mut()
+                .map(|worker| {
+                    let (queue, changed, film, previous, regions) = (&queue, &changed, &film, &previous, &regions);
+                    scope.spawn(move || {
+                        let mut outstanding = Vec::new();
+                        let result = (|| -> io::Result<()> {
+                            loop {
+                                let mut state = queue.lock().expect("[INTERNAL ERROR]: queue lock poisoned");
+                                let available = (worker.threads * QUEUE).saturating_sub(outstanding.len());
+                                let taken = (0..available)
+                                    .map_while(|_| state.pending.pop_front())
+                                    .collect::<Vec<_>>();
+                                if outstanding.is_empty() && taken.is_empty() {
+                                    if state.merged == total { return Ok(()) }
+                                    drop(changed.wait(state).expect("[INTERNAL ERROR]: queue lock poisoned"));
+                                    continue;
+                                }
+                                drop(state);
+
+                                outstanding.extend(&taken);
+                                for index in taken {
+                                    let region = &regions[index];
+                                    progress::BOARD.set(region.column, region.row, progress::State::Active);
+                                    write_request(&mut worker.out, scene, index, &cursor.pass, region, previous)?;
+                                }
+                                worker.out.flush()?;
+
+                                let (index, tile) = read_tile(&mut worker.input)?;
+                                let position = outstanding
+                                    .iter()
+                                    .position(|&outstanding| outstanding == index)
+                                    .ok_or_else(|| checkpoint::invalid("worker returned a tile it was not asked for"))?;
+                                let region = &regions[index];
+                                let mut film = film.lock().expect("[INTERNAL ERROR]: film lock poisoned");
+                                let (ordered, merged) = &mut *film;
+                                if tile.x() + tile.width() > ordered.film().width()
+                                || tile.y() + tile.height() > ordered.film().height()
+                                || tile.splats().iter().any(|splat| {
+                                    splat.x >= ordered.film().width() || splat.y >= ordered.film().height()
+                                }) {
+                                    return Err(checkpoint::invalid("worker returned a tile outside of the film"))
+                                }
+                                outstanding.swap_remove(position);
+                                ordered.push(index, tile);
+                                stats::PIXELS_RENDERED.add(region.width * region.height);
+                                progress::BOARD.set(region.column, region.row, progress::State::Done);
+                                let progress = scene::Cursor { pass: cursor.pass.clone(), tile: ordered.next() };
+                                merged(ordered.film(), &progress);
+                                drop(film);
+
+                                queue.lock().expect("[INTERNAL ERROR]: queue lock poisoned").merged += 1;
+                                changed.notify_all();
+                            }
+                        })();
+
+                        // Give unfinished tiles to the remaining workers
+                        if result.is_err() {
+                            let mut state = queue.lock().expect("[INTERNAL ERROR]: queue lock poisoned");
+                            for index in outstanding {
+                                let region: &schedule::Region = &regions[index];
+                                progress::BOARD.set(region.column, region.row, progress::State::Pending);
+                                state.pending.push_front(index);
+                            }
+                            changed.notify_all();
+                        }
+                        result
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            handles
+                .into_iter()
+                .map(|handle| handle.join().expect("[INTERNAL ERROR]: worker connection panicked"))
+                .collect::<Vec<_>>()
+        });
+
+        let mut failure = None;
+        let mut results = results.into_iter();
+        self.workers.retain(|worker| match results.next() {
+        | Some(Err(error)) => {
+            failure = Some(io::Error::new(error.kind(), format!("{}: {}", worker.address, error)));
+            false
+        }
+        | _ => true,
+        });
+
+        let queue = queue.into_inner().expect("[INTERNAL ERROR]: queue lock poisoned");
+        match failure {
+        | Some(error) if queue.merged < total => Err(error),
+        | _ => Ok(()),
+        }
+    }
+}
+
+fn write_request<W: Write>(
+    mut out: W,
+    scene: &scene::Scene,
+    index: usize,
+    pass: &ops::Range<usize>,
+    region: &schedule::Region,
+    previous: &[adaptive::Estimate],
+) -> io::Result<()> {
+    let width = scene.width();
+    out.write_u8(RENDER)?;
+    out.write_u64::<LE>(index as u64)?;
+    out.write_u64::<LE>(scene.shot() as u64)?;
+    out.write_u64::<LE>(scene.frame() as u64)?;
+    out.write_u64::<LE>(pass.start as u64)?;
+    out.write_u64::<LE>(pass.end as u64)?;
+    if previous.is_empty() {
+        return out.write_u64::<LE>(0)
+    }
+    out.write_u64::<LE>((region.width * region.height) as u64)?;
+    for y in region.y..region.y + region.height {
+        for estimate in &previous[y * width + region.x..y * width + region.x + region.width] {
+            checkpoint::write_estimate(&mut out, estimate)?;
+        }
+    }
+    Ok(())
+}
+
+fn write_tile<W: Write>(mut out: W, index: usize, tile: &film::Tile) -> io::Result<()> {
+    out.write_u64::<LE>(index as u64)?;
+    out.write_u64::<LE>(tile.x() as u64)?;
+    out.write_u64::<LE>(tile.y() as u64)?;
+    out.write_u64::<LE>(tile.width() as u64)?;
+    out.write_u64::<LE>(tile.height() as u64)?;
+    for pixel in tile.pixels() {
+        checkpoint::write_pixel(&mut out, pixel)?;
+    }
+    out.write_u64::<LE>(tile.splats().len() as u64)?;
+    for splat in tile.splats() {
+        out.write_u64::<LE>(splat.x as u64)?;
+        out.write_u64::<LE>(splat.y as u64)?;
+        out.write_f32::<LE>(splat.radiance.x())?;
+        out.write_f32::<LE>(splat.radiance.y())?;
+        out.write_f32::<LE>(splat.radiance.z())?;
+    }
+    out.flush()
+}
+
+fn read_tile<R: Read>(mut input: R) -> io::Result<(usize, film::Tile)> {
+    let index = input.read_u64::<LE>()? as usize;
+    let x = input.read_u64::<LE>()? as usize;
+    let y = input.read_u64::<LE>()? as usize;
+    let width = input.read_u64::<LE>()? as usize;
+    let height = input.read_u64::<LE>()? as usize;
+    let pixels = (0..width * height)
+        .map(|_| checkpoint::read_pixel(&mut input))
+        .collect::<io::Result<Vec<_>>>()?;
+    let mut tile = film::Tile::from_pixels(x, y, width, height, pixels);
+    let splats = (0..input.read_u64::<LE>()?)
+        .map(|_| {
+            let x = input.read_u64::<LE>()? as usize;
+            let y = input.read_u64::<LE>()? as usize;
+            let radiance = math::Vec3::new(
+                input.read_f32::<LE>()?,
+                input.read_f32::<LE>()?,
+                input.read_f32::<LE>()?,
+            );
+            Ok(film::Splat::new(x, y, radiance))
+        })
+        .collect::<io::Result<Vec<_>>>()?;
+    tile.add_splats(splats);
+    Ok((index, tile))
+}
+
+/// Tell the coordinator why its scene could not be loaded.
+fn refuse<W: Write>(mut out: W, message: &str) -> io::Error {
+    out.write_u8(FAILED)
+        .and_then(|_| write_bytes(&mut out, message.as_bytes()))
+        .and_then(|_| out.flush())
+        .ok();
+    io::Error::other(message.to_owned())
+}
+
+fn write_bytes<W: Write>(mut out: W, bytes: &[u8]) -> io::Result<()> {
+    out.write_u64::<LE>(bytes.len() as u64)?;
+    out.write_all(bytes)
+}
+
+fn read_bytes<R: Read>(mut input: R) -> io::Result<Vec<u8>> {
+    let len = input.read_u64::<LE>()?;
+    let mut bytes = Vec::new();
+    input.take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(io::ErrorKind::UnexpectedEof.into())
+    }
+    Ok(bytes)
+}
+
+fn read_string<R: Read>(input: R) -> io::Result<String> {
+    String::from_utf8(read_bytes(input)?)
+        .map_err(|_| checkpoint::invalid("expected UTF-8 text"))
+}
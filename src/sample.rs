@@ -1,33 +1,134 @@
-#![allow(unused)]
+use std::fmt;
 
-use std::ops::BitXor;
+mod independent;
+mod rng;
+mod sobol;
+mod stratified;
 
-const MIN: f32 = 2.3283064365386963e-10;
-const MAX: f32 = 1.0f32 - f32::EPSILON;
+pub use independent::Independent;
+pub use rng::Rng;
+pub use sobol::Sobol;
+pub use stratified::Stratified;
 
-const COLS: usize = 52;
-const DIMS: usize = 1024;
+/// Largest float strictly less than one.
+pub const ONE_MINUS_EPSILON: f32 = 1.0 - f32::EPSILON / 2.0;
 
-static MATRICES: [u32; DIMS * COLS] = include!("../data/sobol.txt");
+/// Source of sample values in `[0, 1)`, organized into one stream per pixel sample.
+///
+/// Each call to `get_1d` or `get_2d` consumes the next dimension(s) of the
+/// current sample, so callers must request dimensions in a consistent order.
+pub trait Sampler: fmt::Debug + Send + Sync {
+    /// Select the family of sample streams, so that different seeds give
+    /// independent renders and equal seeds give identical ones.
+    fn set_seed(&mut self, seed: u64);
+
+    /// Begin pixel `(x, y)`, which will receive `samples` samples.
+    fn start_pixel(&mut self, x: usize, y: usize, samples: usize);
+
+    /// Begin the `index`-th sample of the current pixel, rewinding to the first dimension.
+    fn start_sample(&mut self, index: usize);
+
+    fn get_1d(&mut self) -> f32;
+
+    fn get_2d(&mut self) -> (f32, f32);
+}
+
+impl<S> Sampler for &mut S where S: Sampler + ?Sized {
+    fn set_seed(&mut self, seed: u64) {
+        (**self).set_seed(seed)
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize, samples: usize) {
+        (**self).start_pixel(x, y, samples)
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        (**self).start_sample(index)
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        (**self).get_1d()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (**self).get_2d()
+    }
+}
 
-/// Sobol sampler
 #[derive(Clone, Debug)]
-pub struct Sobol;
-
-impl Sobol {
-    fn sample(
-        mut input: u64,
-        dimension: usize,
-    ) -> f32 {
-        let mut output = 0;
-        let mut column = dimension * COLS;
-        while input != 0 {
-            if input & 1 > 0 {
-                output ^= MATRICES[column];
-            }
-            input >>= 1;
-            column += 1;
+pub enum Any {
+    Independent(Independent),
+    Stratified(Stratified),
+    Sobol(Sobol),
+}
+
+impl Default for Any {
+    fn default() -> Self {
+        Any::Sobol(Sobol::default())
+    }
+}
+
+impl Sampler for Any {
+    fn set_seed(&mut self, seed: u64) {
+        match self {
+            Any::Independent(sampler) => sampler.set_seed(seed),
+            Any::Stratified(sampler) => sampler.set_seed(seed),
+            Any::Sobol(sampler) => sampler.set_seed(seed),
+        }
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize, samples: usize) {
+        match self {
+            Any::Independent(sampler) => sampler.start_pixel(x, y, samples),
+            Any::Stratified(sampler) => sampler.start_pixel(x, y, samples),
+            Any::Sobol(sampler) => sampler.start_pixel(x, y, samples),
+        }
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        match self {
+            Any::Independent(sampler) => sampler.start_sample(index),
+            Any::Stratified(sampler) => sampler.start_sample(index),
+            Any::Sobol(sampler) => sampler.start_sample(index),
+        }
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        match self {
+            Any::Independent(sampler) => sampler.get_1d(),
+            Any::Stratified(sampler) => sampler.get_1d(),
+            Any::Sobol(sampler) => sampler.get_1d(),
         }
-        input as f32 * MIN
     }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        match self {
+            Any::Independent(sampler) => sampler.get_2d(),
+            Any::Stratified(sampler) => sampler.get_2d(),
+            Any::Sobol(sampler) => sampler.get_2d(),
+        }
+    }
+}
+
+/// Mix a sequence of values into a well-distributed 64-bit hash.
+pub fn hash(values: &[u64]) -> u64 {
+    values.iter().fold(0x9e37_79b9_7f4a_7c15, |hash, value| {
+        mix(hash ^ value.wrapping_add(0x9e37_79b9_7f4a_7c15).wrapping_add(hash << 6).wrapping_add(hash >> 2))
+    })
+}
+
+/// MurmurHash3 64-bit finalizer.
+fn mix(mut x: u64) -> u64 {
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    x ^= x >> 33;
+    x = x.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    x ^= x >> 33;
+    x
+}
+
+/// Convert the high bits of `bits` into a float in `[0, 1)`.
+pub fn to_unit(bits: u32) -> f32 {
+    let value = bits as f32 * 2.328_306_4e-10;
+    if value < ONE_MINUS_EPSILON { value } else { ONE_MINUS_EPSILON }
 }
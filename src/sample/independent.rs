@@ -0,0 +1,37 @@
+use crate::sample;
+
+/// Uncorrelated uniform random samples.
+#[derive(Clone, Debug)]
+pub struct Independent {
+    seed: u64,
+    pixel: u64,
+    rng: sample::Rng,
+}
+
+impl Default for Independent {
+    fn default() -> Self {
+        Independent { seed: 0, pixel: 0, rng: sample::Rng::new(0, 0) }
+    }
+}
+
+impl sample::Sampler for Independent {
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize, _: usize) {
+        self.pixel = sample::hash(&[self.seed, x as u64, y as u64]);
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        self.rng = sample::Rng::new(sample::hash(&[self.pixel, index as u64]), self.pixel);
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.rng.next_f32()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.rng.next_f32(), self.rng.next_f32())
+    }
+}
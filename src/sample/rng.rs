@@ -0,0 +1,38 @@
+use crate::sample;
+
+/// PCG32 random number generator.
+///
+/// See: https://www.pcg-random.org/download.html
+#[derive(Copy, Clone, Debug)]
+pub struct Rng {
+    state: u64,
+    increment: u64,
+}
+
+impl Rng {
+    const MULTIPLIER: u64 = 0x5851_f42d_4c95_7f2d;
+
+    /// Generator for `stream`, starting at a position determined by `seed`.
+    pub fn new(seed: u64, stream: u64) -> Self {
+        let mut rng = Rng { state: 0, increment: (stream << 1) | 1 };
+        rng.next_u32();
+        rng.state = rng.state.wrapping_add(seed);
+        rng.next_u32();
+        rng
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        let state = self.state;
+        self.state = state
+            .wrapping_mul(Self::MULTIPLIER)
+            .wrapping_add(self.increment);
+        let xorshift = (((state >> 18) ^ state) >> 27) as u32;
+        let rotate = (state >> 59) as u32;
+        xorshift.rotate_right(rotate)
+    }
+
+    /// Uniformly distributed float in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        sample::to_unit(self.next_u32())
+    }
+}
@@ -0,0 +1,130 @@
+use crate::sample;
+
+const COLS: usize = 52;
+const DIMS: usize = 1024;
+
+static MATRICES: [u32; DIMS * COLS] = include!("../../data/sobol.txt");
+
+/// Sobol sequence with nested uniform (Owen) scrambling.
+///
+/// Each pixel shuffles the sample order and scrambles every dimension
+/// with its own seed, which decorrelates neighboring pixels while
+/// keeping the sequence's stratification. Dimensions past the end of
+/// the direction number table fall back to independent random samples.
+///
+/// See: https://jcgt.org/published/0009/04/01/
+#[derive(Clone, Debug, Default)]
+pub struct Sobol {
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    dimension: usize,
+}
+
+impl Sobol {
+    fn sample(&mut self) -> f32 {
+        let dimension = self.dimension;
+        self.dimension += 1;
+        let seed = sample::hash(&[self.pixel, dimension as u64]) as u32;
+        if dimension < DIMS {
+            sample::to_unit(scramble(sobol(self.index, dimension), seed))
+        } else {
+            sample::to_unit(sample::hash(&[seed as u64, self.index as u64]) as u32)
+        }
+    }
+}
+
+/// Raw `index`-th point of the Sobol sequence in `dimension`.
+fn sobol(mut index: u32, dimension: usize) -> u32 {
+    let mut output = 0;
+    let mut column = dimension * COLS;
+    while index != 0 {
+        if index & 1 > 0 {
+            output ^= MATRICES[column];
+        }
+        index >>= 1;
+        column += 1;
+    }
+    output
+}
+
+/// Nested uniform scramble of the bits of `x`, from most to least significant.
+fn scramble(x: u32, seed: u32) -> u32 {
+    laine_karras(x.reverse_bits(), seed).reverse_bits()
+}
+
+/// Hash that only propagates bits upward, from lowest to highest.
+fn laine_karras(mut x: u32, seed: u32) -> u32 {
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x
+}
+
+impl sample::Sampler for Sobol {
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize, _: usize) {
+        self.pixel = sample::hash(&[self.seed, x as u64, y as u64]);
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        let seed = sample::hash(&[self.pixel]) as u32;
+        self.index = scramble(index as u32, seed);
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.sample(), self.sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sample::Sampler as _;
+
+    /// First `dimensions` values of each of `count` samples of pixel `(x, y)`.
+    fn samples(sampler: &mut Sobol, seed: u64, (x, y): (usize, usize), count: usize, dimensions: usize) -> Vec<f32> {
+        sampler.set_seed(seed);
+        sampler.start_pixel(x, y, count);
+        (0..count)
+            .flat_map(|index| {
+                sampler.start_sample(index);
+                (0..dimensions).map(|_| sampler.get_1d()).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_and_pixel_repeat() {
+        let mut sampler = Sobol::default();
+        let first = samples(&mut sampler, 3, (4, 5), 16, 6);
+        samples(&mut sampler, 3, (6, 7), 16, 6);
+        assert_eq!(samples(&mut Sobol::default(), 3, (4, 5), 16, 6), first);
+        assert_eq!(samples(&mut sampler, 3, (4, 5), 16, 6), first);
+        assert_ne!(samples(&mut sampler, 4, (4, 5), 16, 6), first);
+        assert_ne!(samples(&mut sampler, 3, (5, 4), 16, 6), first);
+    }
+
+    #[test]
+    fn scrambled_samples_stay_in_unit_interval() {
+        // Past the table, dimensions fall back to random samples
+        for value in samples(&mut Sobol::default(), 9, (0, 0), 64, DIMS + 4) {
+            assert!((0.0..1.0).contains(&value), "{}", value);
+        }
+        for x in [0, 1, u32::MAX] {
+            for seed in [0, 1, 0xdead_beef, u32::MAX] {
+                assert!(sample::to_unit(scramble(x, seed)) < 1.0);
+            }
+        }
+    }
+}
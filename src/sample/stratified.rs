@@ -0,0 +1,130 @@
+use crate::math;
+use crate::sample;
+
+/// Jittered samples stratified independently in each dimension, with
+/// strata visited in a different random order per pixel and dimension.
+///
+/// See: https://graphics.pixar.com/library/MultiJitteredSampling/paper.pdf
+#[derive(Clone, Debug, Default)]
+pub struct Stratified {
+    seed: u64,
+    pixel: u64,
+    samples: usize,
+    index: usize,
+    dimension: usize,
+}
+
+impl Stratified {
+    fn sample(&mut self) -> f32 {
+        let dimension = self.dimension as u64;
+        self.dimension += 1;
+
+        let strata = self.samples.max(1) as u32;
+        let seed = sample::hash(&[self.pixel, dimension]);
+        let stratum = permute((self.index % strata as usize) as u32, strata, seed as u32);
+        let jitter = sample::to_unit(sample::hash(&[seed, self.index as u64]) as u32);
+        math::min((stratum as f32 + jitter) / strata as f32, sample::ONE_MINUS_EPSILON)
+    }
+}
+
+/// Bijection on `[0, len)` selected by `seed`.
+fn permute(mut i: u32, len: u32, seed: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= seed;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= seed >> 16;
+        i ^= (i & w) >> 4;
+        i ^= seed >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= seed >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | seed >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len { break }
+    }
+    i.wrapping_add(seed) % len
+}
+
+impl sample::Sampler for Stratified {
+    fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    fn start_pixel(&mut self, x: usize, y: usize, samples: usize) {
+        self.pixel = sample::hash(&[self.seed, x as u64, y as u64]);
+        self.samples = samples;
+    }
+
+    fn start_sample(&mut self, index: usize) {
+        self.index = index;
+        self.dimension = 0;
+    }
+
+    fn get_1d(&mut self) -> f32 {
+        self.sample()
+    }
+
+    fn get_2d(&mut self) -> (f32, f32) {
+        (self.sample(), self.sample())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sample::Sampler as _;
+
+    /// First `dimensions` values of each of `count` samples of pixel `(x, y)`.
+    fn samples(sampler: &mut Stratified, seed: u64, (x, y): (usize, usize), count: usize, dimensions: usize) -> Vec<f32> {
+        sampler.set_seed(seed);
+        sampler.start_pixel(x, y, count);
+        (0..count)
+            .flat_map(|index| {
+                sampler.start_sample(index);
+                (0..dimensions).map(|_| sampler.get_1d()).collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn same_seed_and_pixel_repeat() {
+        let mut sampler = Stratified::default();
+        let first = samples(&mut sampler, 3, (4, 5), 16, 6);
+        samples(&mut sampler, 3, (6, 7), 16, 6);
+        assert_eq!(samples(&mut Stratified::default(), 3, (4, 5), 16, 6), first);
+        assert_eq!(samples(&mut sampler, 3, (4, 5), 16, 6), first);
+        assert_ne!(samples(&mut sampler, 4, (4, 5), 16, 6), first);
+        assert_ne!(samples(&mut sampler, 3, (5, 4), 16, 6), first);
+    }
+
+    #[test]
+    fn one_sample_per_stratum() {
+        let (count, dimensions) = (16, 4);
+        let values = samples(&mut Stratified::default(), 0, (1, 2), count, dimensions);
+        for dimension in 0..dimensions {
+            let mut strata = values
+                .iter()
+                .skip(dimension)
+                .step_by(dimensions)
+                .inspect(|value| assert!((0.0..1.0).contains(*value)))
+                .map(|value| (value * count as f32) as usize)
+                .collect::<Vec<_>>();
+            strata.sort_unstable();
+            assert_eq!(strata, (0..count).collect::<Vec<_>>());
+        }
+    }
+}
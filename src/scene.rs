@@ -1,25 +1,41 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::Hasher as _;
 use std::io;
 use std::path;
 use std::fs;
+use std::ops;
+use std::sync;
+use std::time;
 
 use rayon::prelude::*;
 
 use crate::prelude::*;
+use crate::adaptive;
 use crate::arena;
 use crate::bvh;
+use crate::bxdf;
 use crate::camera;
+use crate::film;
+use crate::filter;
 use crate::geom;
 use crate::light;
 use crate::integrator;
 use crate::math;
+use crate::progress;
+use crate::sample;
+use crate::schedule;
 use crate::scene;
 use crate::stats;
+use crate::tone;
 
+mod error;
 mod token;
 mod lexer;
 mod parser;
 
-pub use token::Token;
+pub use error::{Error, ErrorKind};
+pub use token::{Position, Token};
 pub use lexer::Lexer;
 pub use parser::Parser;
 
@@ -28,18 +44,44 @@ pub struct Scene<'scene> {
     width: usize,
     height: usize,
     samples: usize,
-    camera: camera::Camera,
+    shots: Vec<Shot>,
+
+    /// Aspect ratio that overrides every camera's own
+    aspect: Option<f32>,
+
+    frames: usize,
+    shot: usize,
+    frame: usize,
+    shutter: camera::Shutter,
     lights: Vec<&'scene light::Any<'scene>>,
-    surface: bvh::Tree<'scene, &'scene geom::Any<'scene>>,
+    surface: bvh::Tree<'scene, geom::Object<'scene>>,
     integrator: &'scene integrator::Any,
+    tone: tone::Tone,
+    filter: filter::Any,
+    sampler: sample::Any,
+    seed: u64,
+    adaptive: Option<adaptive::Adaptive>,
+    schedule: schedule::Schedule,
+    crop: Option<film::Crop>,
+    photons: integrator::PhotonCache,
+
+    /// Fingerprint of the cameras, lights and surfaces, as parsed
+    contents: u64,
+
+    /// Material IDs, by address of their BxDF
+    materials: HashMap<usize, usize>,
 }
 
 impl<'scene> Scene<'scene> {
-    pub fn load<P: AsRef<path::Path>>(arena: &'scene arena::Arena, path: P) -> io::Result<Self> {
-        let file = fs::File::open(path)?;
-        let lexer = scene::Lexer::new(file);
-        let mut parser = scene::Parser::new(arena, lexer);
-        Ok(parser.parse_scene())
+    pub fn load<P: AsRef<path::Path>>(arena: &'scene arena::Arena, path: P) -> Result<Self, scene::Error> {
+        let path = path.as_ref();
+        let file = fs::File::open(path)
+            .map_err(scene::Error::from)
+            .map_err(|error| error.with_path(path))?;
+        let lexer = scene::Lexer::new(io::BufReader::new(file));
+        let mut parser = scene::Parser::new(arena, lexer).with_path(path);
+        parser.parse_scene()
+            .map_err(|error| error.with_path(path))
     }
 
     pub fn new(
@@ -47,13 +89,48 @@ impl<'scene> Scene<'scene> {
         width: usize,
         height: usize,
         samples: usize,
-        camera: camera::Camera,
+        shots: Vec<Shot>,
         lights: Vec<&'scene light::Any>,
         surfaces: Vec<&'scene geom::Any<'scene>>,
         integrator: &'scene integrator::Any,
     ) -> Self {
-        let surface = bvh::Tree::new(arena, &surfaces);
-        Scene { width, height, samples, camera, lights, surface, integrator }
+        assert!(!shots.is_empty(), "[INTERNAL ERROR]: scene requires at least one camera");
+        let objects = surfaces.iter()
+            .enumerate()
+            .map(|(id, surface)| geom::Object::new(id, surface))
+            .collect::<Vec<_>>();
+        let surface = bvh::Tree::new(arena, &objects);
+        let tone = tone::Tone::default();
+        let filter = filter::Any::default();
+        let sampler = sample::Any::default();
+        let seed = 0;
+        let adaptive = None;
+        let schedule = schedule::Schedule::default();
+        let crop = None;
+        Scene {
+            width,
+            height,
+            samples,
+            shots,
+            aspect: None,
+            frames: 1,
+            shot: 0,
+            frame: 1,
+            shutter: camera::Shutter::default(),
+            lights,
+            surface,
+            integrator,
+            tone,
+            filter,
+            sampler,
+            seed,
+            adaptive,
+            schedule,
+            crop,
+            photons: integrator::PhotonCache::default(),
+            contents: 0,
+            materials: HashMap::default(),
+        }
     }
 
     pub fn lights(&self) -> &[&'scene light::Any<'scene>] {
@@ -68,35 +145,478 @@ impl<'scene> Scene<'scene> {
         self.height
     }
 
-    pub fn render(&self) {
-        let mut buffer = vec![0; self.width * self.height * 3];
-        buffer.par_chunks_mut(self.width * 3)
+    pub fn samples(&self) -> usize {
+        self.samples
+    }
+
+    pub fn set_width(&mut self, width: usize) {
+        self.width = width;
+    }
+
+    pub fn set_height(&mut self, height: usize) {
+        self.height = height;
+    }
+
+    pub fn set_samples(&mut self, samples: usize) {
+        self.samples = samples;
+    }
+
+    /// Cameras to render the scene from, in declaration order.
+    pub fn shots(&self) -> &[Shot] {
+        &self.shots
+    }
+
+    pub fn aspect(&self) -> Option<f32> {
+        self.aspect
+    }
+
+    /// Give every camera the aspect ratio `aspect`, such as that of a resized image.
+    pub fn set_aspect(&mut self, aspect: f32) {
+        self.aspect = Some(aspect);
+        for shot in &mut self.shots {
+            shot.track.set_aspect(aspect);
+        }
+    }
+
+    /// Number of frames in the animation, numbered from one.
+    pub fn frames(&self) -> usize {
+        self.frames
+    }
+
+    pub fn set_frames(&mut self, frames: usize) {
+        self.frames = frames;
+    }
+
+    /// Index of the shot currently being rendered.
+    pub fn shot(&self) -> usize {
+        self.shot
+    }
+
+    /// Number of the frame currently being rendered.
+    pub fn frame(&self) -> usize {
+        self.frame
+    }
+
+    /// Render `frame` of `shot` from now on.
+    pub fn select(&mut self, shot: usize, frame: usize) {
+        assert!(shot < self.shots.len(), "[INTERNAL ERROR]: selected shot does not exist");
+        self.shot = shot;
+        self.frame = frame;
+    }
+
+    pub fn shutter(&self) -> &camera::Shutter {
+        &self.shutter
+    }
+
+    pub fn set_shutter(&mut self, shutter: camera::Shutter) {
+        self.shutter = shutter;
+        self.photons = integrator::PhotonCache::default();
+    }
+
+    pub fn integrator(&self) -> &'scene integrator::Any {
+        self.integrator
+    }
+
+    pub fn set_integrator(&mut self, integrator: &'scene integrator::Any) {
+        self.integrator = integrator;
+        self.photons = integrator::PhotonCache::default();
+    }
+
+    /// Photon map most recently traced for this scene.
+    pub fn photons(&self) -> &integrator::PhotonCache {
+        &self.photons
+    }
+
+    pub fn set_contents(&mut self, contents: u64) {
+        self.contents = contents;
+    }
+
+    /// Number `materials` in order, as the IDs that `material` looks up.
+    pub fn set_materials(&mut self, materials: &[&'scene bxdf::Any<'scene>]) {
+        self.materials = materials.iter()
             .enumerate()
-            .map(|(y, row)| (self.height - y - 1, row))
-            .for_each(|(y, row)| {
-                let mut hit = geom::Hit::default();
-                for x in 0..self.width {
-                    let mut c = math::Vec3::default();
-                    for _ in 0..self.samples {
-                        let u = (x as f32 + rand::random::<f32>()) / self.width as f32;
-                        let v = (y as f32 + rand::random::<f32>()) / self.height as f32;
-                        let mut r = self.camera.get(u, v);
-                        if self.hit(&mut r, &mut hit) {
-                            c += self.integrator.shade(self, &r, &hit, 0);
-                        }
-                    }
+            .map(|(id, bxdf)| (*bxdf as *const bxdf::Any as usize, id))
+            .collect();
+    }
 
-                    c /= self.samples as f32;
-                    c = c.sqrt();
+    /// ID of `bxdf`, if it is one of the scene's materials.
+    pub fn material(&self, bxdf: &bxdf::Any) -> Option<usize> {
+        self.materials.get(&(bxdf as *const bxdf::Any as usize)).copied()
+    }
 
-                    row[x * 3 + 0] = (math::clamp(c.r(), 0.0, 1.0) * 255.99) as u8;
-                    row[x * 3 + 1] = (math::clamp(c.g(), 0.0, 1.0) * 255.99) as u8;
-                    row[x * 3 + 2] = (math::clamp(c.b(), 0.0, 1.0) * 255.99) as u8;
-                    stats::PIXELS_RENDERED.inc();
+    pub fn tone(&self) -> &tone::Tone {
+        &self.tone
+    }
+
+    pub fn set_tone(&mut self, tone: tone::Tone) {
+        self.tone = tone;
+    }
+
+    pub fn filter(&self) -> &filter::Any {
+        &self.filter
+    }
+
+    pub fn set_filter(&mut self, filter: filter::Any) {
+        self.filter = filter;
+    }
+
+    pub fn sampler(&self) -> &sample::Any {
+        &self.sampler
+    }
+
+    pub fn set_sampler(&mut self, sampler: sample::Any) {
+        self.sampler = sampler;
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn set_seed(&mut self, seed: u64) {
+        self.seed = seed;
+    }
+
+    pub fn adaptive(&self) -> Option<&adaptive::Adaptive> {
+        self.adaptive.as_ref()
+    }
+
+    pub fn set_adaptive(&mut self, adaptive: Option<adaptive::Adaptive>) {
+        self.adaptive = adaptive;
+    }
+
+    pub fn schedule(&self) -> &schedule::Schedule {
+        &self.schedule
+    }
+
+    pub fn set_schedule(&mut self, schedule: schedule::Schedule) {
+        self.schedule = schedule;
+    }
+
+    pub fn set_crop(&mut self, crop: Option<film::Crop>) {
+        self.crop = crop;
+    }
+
+    /// Pixels of the crop window, if any.
+    pub fn crop(&self) -> Option<film::Bounds> {
+        self.crop.map(|crop| crop.resolve(self.width, self.height))
+    }
+
+    /// Pixels that receive samples: the crop window, plus a margin so
+    /// that pixels on its border see samples from every side.
+    pub fn bounds(&self) -> film::Bounds {
+        let image = film::Bounds::new(0, 0, self.width, self.height);
+        match self.crop() {
+        | Some(crop) => crop.expand(self.filter.radius().ceil() as usize, &image),
+        | None => image,
+        }
+    }
+
+    /// Number of tiles rendered per pass.
+    pub fn tiles(&self) -> usize {
+        let (columns, rows) = self.schedule.grid(&self.bounds());
+        columns * rows
+    }
+
+    /// Accumulate `samples` radiance samples per pixel into `film`,
+    /// spreading each sample over its neighbors with the reconstruction filter.
+    /// With adaptive sampling enabled, `samples` is only an upper bound.
+    ///
+    /// The result depends only on the scene and its seed, not on the number of threads.
+    pub fn render(&self, film: &mut film::Film) {
+        let passes = self.passes(&Cursor::default(), false);
+        self.render_passes(film, &passes, None, |_, _| ());
+    }
+
+    /// Plan the passes that finish a render stopped at `from`: the rest of its
+    /// current pass, then either the remaining sample budget in one pass or,
+    /// if `progressive`, passes of 1, 2, 4, ... samples per pixel. Integrators
+    /// that gather from a new photon map every sample get one pass per sample.
+    pub fn passes(&self, from: &Cursor, progressive: bool) -> Vec<Cursor> {
+        let mut passes = Vec::new();
+        if !from.pass.is_empty() && from.tile < self.tiles() {
+            passes.push(from.clone());
+        }
+        let mut start = from.pass.end;
+        while start < self.samples {
+            let end = if self.integrator.per_sample() {
+                start + 1
+            } else if progressive {
+                let mut end = 1;
+                while end <= start { end *= 2 }
+                end.min(self.samples)
+            } else {
+                self.samples
+            };
+            passes.push(Cursor { pass: start..end, tile: 0 });
+            start = end;
+        }
+        passes
+    }
+
+    /// Number of pixels that `passes` will sample.
+    pub fn work(&self, passes: &[Cursor]) -> usize {
+        let regions = self.schedule.split(&self.bounds());
+        passes.iter()
+            .flat_map(|pass| &regions[pass.tile.min(regions.len())..])
+            .map(|region| region.width * region.height)
+            .sum()
+    }
+
+    /// Render each of `passes` in turn.
+    ///
+    /// Without an `interval`, `checkpoint` sees the film after every pass but the
+    /// last. With one, it sees the film as soon as `interval` has elapsed since
+    /// the previous checkpoint, including partway through a pass. Either way it
+    /// also receives a cursor from which the render can be resumed.
+    pub fn render_passes<F>(
+        &self,
+        film: &mut film::Film,
+        passes: &[Cursor],
+        interval: Option<time::Duration>,
+        checkpoint: F,
+    ) where F: FnMut(&film::Film, &Cursor) + Send {
+        let render = |film: &mut film::Film, cursor: &Cursor, merged: &mut (dyn FnMut(&film::Film, &Cursor) + Send)| {
+            self.render_pass(film, cursor, merged);
+            Ok(())
+        };
+        self.render_passes_with(film, passes, interval, render, checkpoint)
+            .expect("[INTERNAL ERROR]: local rendering cannot fail");
+    }
+
+    /// Like [`render_passes`](Scene::render_passes), but with each pass
+    /// rendered by `render`, which has the same contract as [`render_pass`](Scene::render_pass).
+    pub fn render_passes_with<R, F>(
+        &self,
+        film: &mut film::Film,
+        passes: &[Cursor],
+        interval: Option<time::Duration>,
+        mut render: R,
+        mut checkpoint: F,
+    ) -> io::Result<()>
+    where R: FnMut(&mut film::Film, &Cursor, &mut (dyn FnMut(&film::Film, &Cursor) + Send)) -> io::Result<()>,
+          F: FnMut(&film::Film, &Cursor) + Send,
+    {
+        let last = passes.len().saturating_sub(1);
+        let tiles = self.tiles();
+        let mut written = time::Instant::now();
+        for (index, cursor) in passes.iter().enumerate() {
+            match interval {
+            | None => {
+                render(film, cursor, &mut |_, _| ())?;
+                if index < last {
+                    checkpoint(film, &Cursor { pass: cursor.pass.clone(), tile: tiles });
                 }
+            }
+            | Some(interval) => {
+                render(film, cursor, &mut |film, cursor| {
+                    if written.elapsed() >= interval {
+                        checkpoint(film, cursor);
+                        written = time::Instant::now();
+                    }
+                })?;
+            }
+            }
+        }
+        Ok(())
+    }
+
+    /// Trace the photon maps that samples `pass` of `frame` gather from, so that
+    /// tiles find them ready rather than waiting on or duplicating each other.
+    ///
+    /// Must not be called from the thread pool, which tracing the photons uses.
+    pub fn trace_photons(&self, frame: usize, pass: ops::Range<usize>) {
+        for index in pass {
+            self.integrator.photons(self, frame, index);
+        }
+    }
+
+    /// Accumulate samples `cursor.pass` into every pixel of the tiles from `cursor.tile`
+    /// onward, calling `merged` with the film and its progress each time another tile
+    /// is merged into it.
+    ///
+    /// Rendering consecutive ranges yields the same samples as rendering
+    /// their union at once, so passes can be split up or resumed freely.
+    pub fn render_pass<F>(&self, film: &mut film::Film, cursor: &Cursor, merged: F)
+    where F: FnMut(&film::Film, &Cursor) + Send {
+        assert!(
+            film.width() == self.width && film.height() == self.height,
+            "[INTERNAL ERROR]: film does not match scene dimensions",
+        );
+
+        // Statistics from earlier passes, so converged pixels stay converged
+        let previous = match self.adaptive {
+        | Some(_) => film.pixels().iter().map(film::Pixel::estimate).collect(),
+        | None => Vec::new(),
+        };
+
+        let bounds = self.bounds();
+        let (columns, rows) = self.schedule.grid(&bounds);
+        let regions = self.schedule.split(&bounds);
+        progress::BOARD.reset(columns, rows);
+        for region in &regions[..cursor.tile.min(regions.len())] {
+            progress::BOARD.set(region.column, region.row, progress::State::Done);
+        }
+
+        if cursor.tile < regions.len() {
+            self.trace_photons(self.frame, cursor.pass.clone());
+        }
+
+        let film = sync::Mutex::new((film::Ordered::new(film, cursor.tile), merged));
+
+        regions.iter()
+            .enumerate()
+            .skip(cursor.tile)
+            .par_bridge()
+            .for_each(|(index, region)| {
+                progress::BOARD.set(region.column, region.row, progress::State::Active);
+                let tile = self.render_tile(self.shot, self.frame, region, cursor.pass.clone(), |x, y| {
+                    previous.get(y * self.width + x).copied().unwrap_or_default()
+                });
+                let mut film = film.lock().expect("[INTERNAL ERROR]: film lock poisoned");
+                let (ordered, merged) = &mut *film;
+                ordered.push(index, tile);
+                progress::BOARD.set(region.column, region.row, progress::State::Done);
+                let progress = Cursor { pass: cursor.pass.clone(), tile: ordered.next() };
+                merged(ordered.film(), &progress);
             });
+    }
+
+    /// Take samples `pass` of `frame` through camera `shot` in every pixel of `region`,
+    /// returning them in a tile padded by the filter radius. `previous` gives the statistics
+    /// of a pixel's earlier samples, which adaptive sampling needs to decide when to stop.
+    pub fn render_tile<F>(
+        &self,
+        shot: usize,
+        frame: usize,
+        region: &schedule::Region,
+        pass: ops::Range<usize>,
+        previous: F,
+    ) -> film::Tile where F: Fn(usize, usize) -> adaptive::Estimate {
+        // Samples from a tile can land this many pixels outside of it
+        let pad = self.filter.radius().ceil() as usize;
+        let x0 = region.x.saturating_sub(pad);
+        let y0 = region.y.saturating_sub(pad);
+        let x1 = (region.x + region.width + pad).min(self.width);
+        let y1 = (region.y + region.height + pad).min(self.height);
+        let mut tile = film::Tile::new(x0, y0, x1 - x0, y1 - y0);
+        let track = &self.shots[shot].track;
+        let mut sampler = self.sampler.clone();
+        sampler.set_seed(self.seed);
+        let mut hit = geom::Hit::default();
+        let mut splats = Vec::new();
+        let photons = pass.clone()
+            .map(|index| self.integrator.photons(self, frame, index))
+            .collect::<Vec<_>>();
+        for y in region.y..region.y + region.height {
+            for x in region.x..region.x + region.width {
+                let mut total = previous(x, y);
+                let mut taken = adaptive::Estimate::default();
+                sampler.start_pixel(x, y, self.samples);
+                for index in pass.clone() {
+                    if let Some(adaptive) = &self.adaptive {
+                        if adaptive.converged(&total) { break }
+                    }
+                    sampler.start_sample(index);
+                    let (jx, jy) = sampler.get_2d();
+                    let fx = x as f32 + jx;
+                    let fy = y as f32 + jy;
+                    let u = fx / self.width as f32;
+                    let v = 1.0 - fy / self.height as f32;
+                    let time = self.shutter.sample(frame, &mut sampler);
+                    let camera = track.at(time);
+                    let mut r = camera
+                        .get(u, v, &mut sampler)
+                        .with_time(time);
+                    let view = integrator::View {
+                        camera: &camera,
+                        photons: photons[index - pass.start].as_deref(),
+                    };
+                    let c = self.integrator.trace(self, &view, &mut r, &mut hit, &mut sampler, &mut splats);
+                    tile.splat(&self.filter, fx, fy, &c);
+                    total.add(tone::luminance(&c));
+                    taken.add(tone::luminance(&c));
+                }
+                tile.record(x, y, &taken);
+                stats::PIXELS_RENDERED.inc();
+            }
+        }
+        tile.add_splats(splats);
+        tile
+    }
+
+    /// Fingerprint of everything that determines which samples a render takes.
+    ///
+    /// The cameras, lights and surfaces are covered by the fingerprint the parser took
+    /// of their source, while the settings that can be overridden afterward are hashed
+    /// by value. The sample budget, seed, adaptive sampling and tone mapping are left out,
+    /// since a resumed render may change them without invalidating earlier work.
+    pub fn hash(&self) -> u64 {
+        use std::fmt::Write as _;
+        let mut hasher = Fnv::default();
+        hasher.write_u64(self.contents);
+        write!(
+            hasher,
+            "{} {} {:?} {} {} {:?} {:?} {:?} {:?} {:?} {:?}",
+            self.width,
+            self.height,
+            self.aspect,
+            self.shot,
+            self.frame,
+            self.shutter,
+            self.integrator,
+            self.filter,
+            self.sampler,
+            self.schedule,
+            self.crop(),
+        ).expect("[INTERNAL ERROR]: hashing cannot fail");
+        hasher.finish()
+    }
+}
+
+/// Camera that the scene can be rendered from.
+#[derive(Clone, Debug)]
+pub struct Shot {
+    /// Name to tell the shot's images apart, if there is more than one shot
+    pub name: Option<String>,
+
+    pub track: camera::Track,
+}
+
+/// How far a render has progressed: every pixel has received the samples
+/// numbered before `pass.start`, and the first `tile` tiles in scheduling
+/// order have also received `pass`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Cursor {
+    pub pass: ops::Range<usize>,
+    pub tile: usize,
+}
+
+/// 64-bit FNV-1a hash over bytes or formatted text.
+struct Fnv(u64);
+
+impl Default for Fnv {
+    fn default() -> Self {
+        Fnv(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl std::hash::Hasher for Fnv {
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u64;
+            self.0 = self.0.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        self.0
+    }
+}
 
-        lodepng::encode24_file("out.png", &buffer, self.width, self.height).ok();
+impl fmt::Write for Fnv {
+    fn write_str(&mut self, string: &str) -> fmt::Result {
+        self.write(string.as_bytes());
+        Ok(())
     }
 }
 
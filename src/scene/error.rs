@@ -0,0 +1,132 @@
+use std::error;
+use std::fmt;
+use std::io;
+use std::path;
+
+use crate::scene;
+
+/// Failure to load a scene, annotated with its source location when known.
+#[derive(Debug)]
+pub struct Error {
+    /// Scene file containing the error
+    path: Option<path::PathBuf>,
+
+    /// Location of the offending token
+    position: Option<scene::Position>,
+
+    /// Cause of the error
+    kind: ErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ErrorKind {
+    /// Failed to read the scene file
+    Io(io::Error),
+
+    /// Malformed numeric literal
+    Number(String),
+
+    /// String literal missing its closing quote
+    Quote,
+
+    /// File includes itself, directly or indirectly
+    Cycle(path::PathBuf),
+
+    /// Reference to a constant or material that was never defined
+    Undefined(String),
+
+    /// Scene ended without a required element
+    Missing(&'static str),
+
+    /// Camera name that was already given to another camera
+    Duplicate(String),
+
+    /// Transform block collapses space and cannot be inverted
+    Singular,
+
+    /// Token did not match the expected production, or the file ended early
+    Unexpected {
+        expected: &'static str,
+        found: Option<scene::Token>,
+    },
+}
+
+impl Error {
+    pub fn new(position: scene::Position, kind: ErrorKind) -> Self {
+        Error { path: None, position: Some(position), kind }
+    }
+
+    pub fn unexpected(
+        position: scene::Position,
+        expected: &'static str,
+        found: Option<scene::Token>,
+    ) -> Self {
+        Error::new(position, ErrorKind::Unexpected { expected, found })
+    }
+
+    /// Attribute this error to the scene file at `path`, unless
+    /// it has already been attributed to another file.
+    pub fn with_path<P: AsRef<path::Path>>(mut self, path: P) -> Self {
+        if self.path.is_none() {
+            self.path = Some(path.as_ref().to_path_buf());
+        }
+        self
+    }
+
+    pub fn path(&self) -> Option<&path::Path> {
+        self.path.as_deref()
+    }
+
+    pub fn position(&self) -> Option<scene::Position> {
+        self.position
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(error: io::Error) -> Self {
+        Error { path: None, position: None, kind: ErrorKind::Io(error) }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        if let Some(path) = &self.path {
+            write!(fmt, "{}:", path.display())?;
+        }
+        if let Some(position) = &self.position {
+            write!(fmt, "{}:", position)?;
+        }
+        if self.path.is_some() || self.position.is_some() {
+            write!(fmt, " ")?;
+        }
+        match &self.kind {
+        | ErrorKind::Io(error) => write!(fmt, "{}", error),
+        | ErrorKind::Number(literal) => write!(fmt, "invalid numeric literal '{}'", literal),
+        | ErrorKind::Quote => write!(fmt, "unterminated string literal"),
+        | ErrorKind::Cycle(path) => write!(fmt, "recursive include of '{}'", path.display()),
+        | ErrorKind::Undefined(name) => write!(fmt, "undefined name '{}'", name),
+        | ErrorKind::Missing(element) => write!(fmt, "scene has no {}", element),
+        | ErrorKind::Duplicate(name) => write!(fmt, "duplicate camera name '{}'", name),
+        | ErrorKind::Singular => write!(fmt, "transform is not invertible"),
+        | ErrorKind::Unexpected { expected, found: Some(token) } => {
+            write!(fmt, "expected {}, found '{}'", expected, token)
+        }
+        | ErrorKind::Unexpected { expected, found: None } => {
+            write!(fmt, "expected {}, found end of file", expected)
+        }
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        match &self.kind {
+        | ErrorKind::Io(error) => Some(error),
+        | _ => None,
+        }
+    }
+}
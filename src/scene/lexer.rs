@@ -6,31 +6,49 @@ pub struct Lexer<R>  {
     buffer: String,
     stream: io::Bytes<R>,
     next: Option<char>,
+    error: Option<io::Error>,
+    position: scene::Position,
 }
 
 impl<R> Lexer<R> where R: io::Read {
     pub fn new(stream: R) -> Self {
-        let mut stream = stream.bytes();
-        let next = stream.next()
-            .map(Result::unwrap)
-            .map(From::from);
-        Lexer {
+        let mut lexer = Lexer {
             buffer: String::default(),
-            stream,
-            next,
-        }
+            stream: stream.bytes(),
+            next: None,
+            error: None,
+            position: scene::Position { line: 1, col: 0 },
+        };
+        Lexer::skip(&mut lexer);
+        lexer
+    }
+
+    /// Location of the next unconsumed character.
+    pub fn position(&self) -> scene::Position {
+        self.position
     }
 
     fn skip(&mut self) {
-        self.next = self.stream.next()
-            .map(Result::unwrap)
-            .map(From::from);
+        if self.next == Some('\n') {
+            self.position.line += 1;
+            self.position.col = 1;
+        } else {
+            self.position.col += 1;
+        }
+        self.next = match self.stream.next() {
+        | Some(Ok(byte)) => Some(From::from(byte)),
+        | Some(Err(error)) => {
+            self.error = Some(error);
+            None
+        }
+        | None => None,
+        };
     }
 
     fn fill(&mut self) {
         self.buffer.clear();
         while let Some(c) = self.next {
-            if c.is_whitespace() { break }
+            if c.is_whitespace() || c == '#' || c == '{' || c == '}' { break }
             self.buffer.push(c);
             self.skip();
         }
@@ -38,40 +56,135 @@ impl<R> Lexer<R> where R: io::Read {
 }
 
 impl<R> Iterator for Lexer<R> where R: io::Read {
-    type Item = scene::Token;
+    type Item = Result<(scene::Position, scene::Token), scene::Error>;
     fn next(&mut self) -> Option<Self::Item> {
 
         while let Some(c) = self.next {
 
             if c.is_whitespace() {
-                self.skip(); 
+                self.skip();
                 continue
             }
 
-            self.fill();      
-            
-            let token = if c.is_digit(10) || c == '-' {
-                self.buffer.parse::<i32>()
+            // Line comments run until the next newline
+            if c == '#' {
+                while let Some(c) = self.next {
+                    if c == '\n' { break }
+                    self.skip();
+                }
+                continue
+            }
+
+            let position = self.position;
+
+            if c == '"' {
+                self.skip();
+                self.buffer.clear();
+                loop {
+                    match self.next {
+                    | Some('"') => break self.skip(),
+                    | Some('\n') | None => {
+                        return Some(Err(scene::Error::new(position, scene::ErrorKind::Quote)))
+                    }
+                    | Some(c) => {
+                        self.buffer.push(c);
+                        self.skip();
+                    }
+                    }
+                }
+                return Some(Ok((position, scene::Token::String(self.buffer.clone()))))
+            }
+
+            // Braces delimit blocks even without surrounding whitespace
+            if c == '{' || c == '}' {
+                self.skip();
+                let token = if c == '{' { scene::Token::LBrace } else { scene::Token::RBrace };
+                return Some(Ok((position, token)))
+            }
+
+            self.fill();
+
+            let token = if c.is_ascii_digit() || c == '-' {
+                match self.buffer.parse::<i32>()
                     .map(scene::Token::Int)
                     .or_else(|_| self.buffer.parse::<f32>().map(scene::Token::Float))
-                    .expect("[SCENE ERROR]: invalid numeric literal")
+                {
+                | Ok(token) => token,
+                | Err(_) => {
+                    let kind = scene::ErrorKind::Number(self.buffer.clone());
+                    return Some(Err(scene::Error::new(position, kind)))
+                }
+                }
             } else {
                 use scene::Token::*;
                 match self.buffer.as_ref() {
                 | "width" => Width,
                 | "height" => Height,
+                | "aspect" => Aspect,
                 | "samples" => Samples,
+                | "define" => Define,
+                | "include" => Include,
+                | "material" => Material,
+                | "prototype" => Prototype,
                 | "camera" => Camera,
+                | "named" => Named,
+                | "key" => Key,
+                | "frames" => Frames,
+                | "shutter" => Shutter,
                 | "integrator" => Integrator,
+                | "tonemap" => Tonemap,
+                | "exposure" => Exposure,
+                | "transfer" => Transfer,
+                | "clamp" => Clamp,
+                | "reinhard" => Reinhard,
+                | "extended" => Extended,
+                | "aces" => ACES,
+                | "gamma" => Gamma,
+                | "srgb" => SRGB,
+                | "filter" => Filter,
+                | "box" => Box,
+                | "tent" => Tent,
+                | "gaussian" => Gaussian,
+                | "mitchell" => Mitchell,
+                | "lanczos" => Lanczos,
+                | "sampler" => Sampler,
+                | "independent" => Independent,
+                | "stratified" => Stratified,
+                | "sobol" => Sobol,
+                | "seed" => Seed,
+                | "adaptive" => Adaptive,
+                | "tiles" => Tiles,
+                | "scanline" => Scanline,
+                | "spiral" => Spiral,
+                | "hilbert" => Hilbert,
+                | "crop" => Crop,
+                | "normalized" => Normalized,
                 | "surface" => Surface,
                 | "light" => Light,
                 | "bxdf" => BxDF,
                 | "normal" => Normal,
                 | "path" => Path,
+                | "bdpt" => BDPT,
+                | "photon" => Photon,
+                | "sppm" => SPPM,
+                | "ao" => AO,
+                | "distance" => Distance,
+                | "uv" => UV,
+                | "albedo" => Albedo,
+                | "object" => Object,
+                | "heatmap" => Heatmap,
+                | "nodes" => Nodes,
+                | "tests" => Tests,
                 | "sphere" => Sphere,
                 | "quad" => Quad,
                 | "point" => Point,
                 | "mesh" => Mesh,
+                | "instance" => Instance,
+                | "moving" => Moving,
+                | "transform" => Transform,
+                | "translate" => Translate,
+                | "rotate" => Rotate,
+                | "scale" => Scale,
                 | "obj" => OBJ,
                 | "stl" => STL,
                 | "glazed" => Glazed,
@@ -82,8 +195,44 @@ impl<R> Iterator for Lexer<R> where R: io::Read {
                 }
             };
 
-            return Some(token)
+            return Some(Ok((position, token)))
         }
-        None
+
+        self.error
+            .take()
+            .map(scene::Error::from)
+            .map(Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// First error from lexing `source`.
+    fn error(source: &str) -> String {
+        Lexer::new(source.as_bytes())
+            .find_map(Result::err)
+            .unwrap()
+            .to_string()
+    }
+
+    #[test]
+    fn positions_count_from_one() {
+        let positions = Lexer::new("width 1\n  {}".as_bytes())
+            .map(|token| token.unwrap().0)
+            .map(|position| (position.line, position.col))
+            .collect::<Vec<_>>();
+        assert_eq!(positions, [(1, 1), (1, 7), (2, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn reports_unterminated_quote() {
+        assert_eq!(error("width 1\n  \"abc\n"), "2:3: unterminated string literal");
+    }
+
+    #[test]
+    fn reports_bad_number() {
+        assert_eq!(error("samples 12x"), "1:9: invalid numeric literal '12x'");
     }
 }
@@ -1,5 +1,12 @@
+use std::collections::HashMap;
+use std::hash::Hash as _;
+use std::hash::Hasher as _;
+use std::fs;
 use std::io;
+use std::path;
+use std::rc::Rc;
 
+use crate::adaptive;
 use crate::arena;
 use crate::camera;
 use crate::bxdf;
@@ -8,38 +15,209 @@ use crate::integrator;
 use crate::light;
 use crate::math;
 use crate::model;
+use crate::sample;
+use crate::schedule;
 use crate::scene;
+use crate::film;
+use crate::filter;
+use crate::tone;
+
+/// Value bound to a name by a `define` directive.
+///
+/// Numbers and vectors are substituted by value wherever they are referenced,
+/// while every reference to a BxDF shares the same one.
+#[derive(Copy, Clone, Debug)]
+enum Constant<'scene> {
+    Float(f32),
+    Vector(math::Vec3),
+    BxDF(&'scene bxdf::Any<'scene>),
+}
+
+/// File format of an external mesh.
+#[allow(clippy::upper_case_acronyms)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+enum Format {
+    OBJ,
+    STL,
+}
+
+/// Scene file pulled in by an `include` directive.
+struct Include {
+    path: Rc<path::Path>,
+    canonical: path::PathBuf,
+    lexer: scene::Lexer<io::BufReader<fs::File>>,
+}
+
+/// Token along with the included file it came from, if any.
+type Spanned = (Option<Rc<path::Path>>, scene::Position, Option<scene::Token>);
 
 pub struct Parser<'scene, R> {
     arena: &'scene arena::Arena,
     lexer: scene::Lexer<R>,
+
+    /// Path of the top-level scene file
+    path: Option<path::PathBuf>,
+
+    /// Stack of files currently being included
+    includes: Vec<Include>,
+
+    /// Included file that produced the most recently consumed token
+    source: Option<Rc<path::Path>>,
+
+    /// Single token of lookahead
+    peeked: Option<Spanned>,
+
+    constants: HashMap<String, Constant<'scene>>,
+
+    /// Named BxDFs shared by every surface that references them
+    materials: HashMap<String, &'scene bxdf::Any<'scene>>,
+
+    /// Every BxDF defined so far, indexed by ID
+    bxdfs: Vec<&'scene bxdf::Any<'scene>>,
+
+    /// Named meshes instanced by `instance` surfaces
+    prototypes: HashMap<String, geom::Prototype<'scene>>,
+
+    /// Meshes already loaded, by format and canonical path
+    models: HashMap<(Format, path::PathBuf), geom::Prototype<'scene>>,
+
+    /// Running hash of the tokens and models that make up the scene's contents
+    contents: scene::Fnv,
+
+    /// Whether the element being parsed is part of the scene's contents
+    hashing: bool,
 }
 
 impl<'scene, R> Parser<'scene, R> where R: io::Read {
     pub fn new(arena: &'scene arena::Arena, lexer: scene::Lexer<R>) -> Self {
-        Parser { arena, lexer }
+        Parser {
+            arena,
+            lexer,
+            path: None,
+            includes: Vec::new(),
+            source: None,
+            peeked: None,
+            constants: HashMap::default(),
+            materials: HashMap::default(),
+            bxdfs: Vec::new(),
+            prototypes: HashMap::default(),
+            models: HashMap::default(),
+            contents: scene::Fnv::default(),
+            hashing: false,
+        }
+    }
+
+    /// Resolve `include` directives relative to the scene file at `path`
+    /// instead of the current working directory.
+    pub fn with_path<P: AsRef<path::Path>>(mut self, path: P) -> Self {
+        self.path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Read the next token from the innermost included file,
+    /// falling back to the top-level file once all includes are exhausted.
+    fn lex(&mut self) -> Result<Spanned, scene::Error> {
+        while let Some(include) = self.includes.last_mut() {
+            match include.lexer.next() {
+            | Some(Ok((position, token))) => return Ok((Some(include.path.clone()), position, Some(token))),
+            | Some(Err(error)) => return Err(error.with_path(&include.path)),
+            | None => { self.includes.pop(); }
+            }
+        }
+        match self.lexer.next() {
+        | Some(Ok((position, token))) => Ok((None, position, Some(token))),
+        | Some(Err(error)) => Err(error),
+        | None => Ok((None, self.lexer.position(), None)),
+        }
     }
 
-    pub fn parse_scene(&mut self) -> scene::Scene<'scene> {
+    /// Consume the next token, or `None` at the end of the stream.
+    fn next(&mut self) -> Result<(scene::Position, Option<scene::Token>), scene::Error> {
+        let (source, position, token) = match self.peeked.take() {
+        | Some(spanned) => spanned,
+        | None => self.lex()?,
+        };
+        self.source = source;
+        if let (true, Some(token)) = (self.hashing, &token) {
+            token.hash(&mut self.contents);
+        }
+        Ok((position, token))
+    }
+
+    /// Inspect the next token without consuming it.
+    fn peek(&mut self) -> Result<Option<scene::Token>, scene::Error> {
+        if self.peeked.is_none() {
+            self.peeked = Some(self.lex()?);
+        }
+        Ok(self.peeked.as_ref().and_then(|(_, _, token)| token.clone()))
+    }
+
+    /// Build an error attributed to the file of the most recently consumed token.
+    fn error(&self, position: scene::Position, kind: scene::ErrorKind) -> scene::Error {
+        let error = scene::Error::new(position, kind);
+        match &self.source {
+        | Some(source) => error.with_path(source),
+        | None => error,
+        }
+    }
+
+    fn unexpected(&self, position: scene::Position, expected: &'static str, found: Option<scene::Token>) -> scene::Error {
+        self.error(position, scene::ErrorKind::Unexpected { expected, found })
+    }
+
+    pub fn parse_scene(&mut self) -> Result<scene::Scene<'scene>, scene::Error> {
         let mut width = 200;
         let mut height = 100;
+        let mut aspect = None;
         let mut samples = 64;
-        let mut camera = camera::Camera::default();
+        let mut camera = None;
+        let mut shots = Vec::new();
+        let mut frames = 1;
+        let mut shutter = camera::Shutter::default();
         let mut lights = Vec::default();
         let mut surfaces = Vec::default();
         let mut integrator = &integrator::Any::Normal(integrator::Normal);
+        let mut tone = tone::Tone::default();
+        let mut filter = filter::Any::default();
+        let mut sampler = sample::Any::default();
+        let mut seed = 0;
+        let mut adaptive = None;
+        let mut schedule = schedule::Schedule::default();
+        let mut crop = None;
 
-        while let Some(item) = self.lexer.next() {
+        let end = loop {
             use scene::Token::*;
-            match item {
-            | Width => width = self.parse_int() as usize,
-            | Height => height = self.parse_int() as usize,
-            | Samples => samples = self.parse_int() as usize,
-            | Camera => {
-                camera = self.parse_camera();
-            }
-            | Light => {
-                match self.parse_light() {
+
+            // Settings may be overridden after parsing, so only cameras, lights,
+            // surfaces and what they refer to are hashed as the scene's contents
+            self.hashing = false;
+            let next = self.next()?;
+            if let (_, Some(token @ (Camera | Light | Surface | Define | Material | Prototype))) = &next {
+                self.hashing = true;
+                token.hash(&mut self.contents);
+            }
+
+            match next {
+            | (position, None) => break position,
+            | (_, Some(Width)) => width = self.parse_int_where("positive width", |width| width > 0)? as usize,
+            | (_, Some(Height)) => height = self.parse_int_where("positive height", |height| height > 0)? as usize,
+            | (_, Some(Aspect)) => aspect = Some(self.parse_float_where("positive aspect ratio", |aspect| aspect > 0.0)?),
+            | (_, Some(Samples)) => samples = self.parse_int_where("positive sample count", |samples| samples > 0)? as usize,
+            | (position, Some(Camera)) => {
+                let shot = self.parse_camera()?;
+                let duplicate = |other: &scene::Shot| other.name.is_some() && other.name == shot.name;
+                match &shot.name {
+                | Some(name) if shots.iter().any(duplicate) => {
+                    return Err(self.error(position, scene::ErrorKind::Duplicate(name.clone())))
+                }
+                | Some(_) => shots.push(shot),
+                | None => camera = Some(shot),
+                }
+            }
+            | (_, Some(Frames)) => frames = self.parse_int_where("positive frame count", |frames| frames > 0)? as usize,
+            | (_, Some(Shutter)) => shutter = self.parse_shutter()?,
+            | (_, Some(Light)) => {
+                match self.parse_light()? {
                 | (light, Some(surface)) => {
                     lights.push(light);
                     surfaces.push(surface);
@@ -49,175 +227,760 @@ impl<'scene, R> Parser<'scene, R> where R: io::Read {
                 }
                 }
             }
-            | Surface => {
-                let surface = self.parse_surface();
+            | (_, Some(Surface)) => {
+                let surface = self.parse_surface()?;
                 surfaces.push(surface);
             }
-            | Integrator => {
-                integrator = self.parse_integrator();
+            | (_, Some(Integrator)) => {
+                integrator = self.parse_integrator()?;
+            }
+            | (_, Some(Tonemap)) => tone.operator = self.parse_tonemap()?,
+            | (_, Some(Exposure)) => tone.exposure = self.parse_float()?,
+            | (_, Some(Transfer)) => tone.transfer = self.parse_transfer()?,
+            | (_, Some(Filter)) => filter = self.parse_filter()?,
+            | (_, Some(Sampler)) => sampler = self.parse_sampler()?,
+            | (_, Some(Seed)) => seed = self.parse_int()? as u64,
+            | (_, Some(Adaptive)) => adaptive = Some(self.parse_adaptive()?),
+            | (_, Some(Tiles)) => schedule = self.parse_schedule()?,
+            | (_, Some(Crop)) => crop = Some(self.parse_crop()?),
+            | (_, Some(Define)) => self.parse_define()?,
+            | (_, Some(Material)) => self.parse_material()?,
+            | (_, Some(Prototype)) => self.parse_prototype()?,
+            | (position, Some(Include)) => self.parse_include(position)?,
+            | (position, found) => {
+                return Err(self.unexpected(position, "top-level element", found))
             }
-            | _ => panic!("[SCENE ERROR]: expected top-level element"),
             }
+        };
+
+        // An unnamed camera comes first
+        match camera {
+        | Some(camera) => shots.insert(0, camera),
+        | None if shots.is_empty() => return Err(self.error(end, scene::ErrorKind::Missing("camera"))),
+        | None => (),
         }
 
-        scene::Scene::new(
+        let mut scene = scene::Scene::new(
             self.arena,
             width,
             height,
             samples,
-            camera,
+            shots,
             lights,
             surfaces,
             integrator,
-        )
+        );
+        scene.set_tone(tone);
+        scene.set_filter(filter);
+        scene.set_sampler(sampler);
+        scene.set_seed(seed);
+        scene.set_adaptive(adaptive);
+        scene.set_schedule(schedule);
+        scene.set_crop(crop);
+        scene.set_frames(frames);
+        if let Some(aspect) = aspect {
+            scene.set_aspect(aspect);
+        }
+        scene.set_shutter(shutter);
+        scene.set_materials(&self.bxdfs);
+        scene.set_contents(self.contents.finish());
+        Ok(scene)
+    }
+
+    fn parse_define(&mut self) -> Result<(), scene::Error> {
+        let name = self.parse_name()?;
+        let constant = match self.peek()? {
+        | Some(scene::Token::Glazed)
+        | Some(scene::Token::Mirror)
+        | Some(scene::Token::Lambertian)
+        | Some(scene::Token::Specular) => Constant::BxDF(self.parse_bxdf()?),
+        | Some(token) if self.reference(&token).is_some() => {
+            let (position, _) = self.next()?;
+            let alias = token.to_string();
+            match self.constants.get(&alias) {
+            | Some(constant) => *constant,
+            | None => return Err(self.error(position, scene::ErrorKind::Undefined(alias))),
+            }
+        }
+        | _ => {
+            let x = self.parse_float()?;
+            match self.peek()? {
+            | Some(scene::Token::Int(_))
+            | Some(scene::Token::Float(_)) => {
+                let y = self.parse_float()?;
+                let z = self.parse_float()?;
+                Constant::Vector(math::Vec3::new(x, y, z))
+            }
+            | _ => Constant::Float(x),
+            }
+        }
+        };
+        self.constants.insert(name, constant);
+        Ok(())
+    }
+
+    fn parse_prototype(&mut self) -> Result<(), scene::Error> {
+        let name = self.parse_name()?;
+        let prototype = self.parse_model()?;
+        self.prototypes.insert(name, prototype);
+        Ok(())
+    }
+
+    fn parse_material(&mut self) -> Result<(), scene::Error> {
+        let name = self.parse_name()?;
+        let bxdf = self.parse_bxdf()?;
+        self.materials.insert(name, bxdf);
+        Ok(())
+    }
+
+    fn parse_include(&mut self, position: scene::Position) -> Result<(), scene::Error> {
+        let file = self.parse_string()?;
+        let path = self.resolve(&file);
+        let open = fs::File::open(&path)
+            .and_then(|file| Ok((file, path.canonicalize()?)));
+
+        let (file, canonical) = match open {
+        | Ok(open) => open,
+        | Err(error) => {
+            let error = io::Error::new(
+                error.kind(),
+                format!("could not include '{}': {}", path.display(), error),
+            );
+            return Err(self.error(position, scene::ErrorKind::Io(error)))
+        }
+        };
+
+        let cycle = self.path
+            .as_ref()
+            .and_then(|path| path.canonicalize().ok())
+            .iter()
+            .chain(self.includes.iter().map(|include| &include.canonical))
+            .any(|included| *included == canonical);
+
+        if cycle {
+            return Err(self.error(position, scene::ErrorKind::Cycle(path)))
+        }
+
+        self.includes.push(Include {
+            path: Rc::from(path),
+            canonical,
+            lexer: scene::Lexer::new(io::BufReader::new(file)),
+        });
+
+        Ok(())
+    }
+
+    /// Resolve `file` relative to the directory of the file currently being parsed.
+    fn resolve(&self, file: &str) -> path::PathBuf {
+        let base = match &self.source {
+        | Some(source) => source.parent(),
+        | None => self.path.as_deref().and_then(path::Path::parent),
+        };
+        match base {
+        | Some(base) => base.join(file),
+        | None => path::PathBuf::from(file),
+        }
     }
 
-    fn parse_camera(&mut self) -> camera::Camera {
-        let origin = self.parse_vec();
-        let toward = self.parse_vec();
-        let up = self.parse_vec();
-        let fov = self.parse_float();
-        let aspect = self.parse_float();
-        let aperture = self.parse_float();
-        let focus = self.parse_float();
-        camera::Camera::new(origin, toward, up, fov, aspect, aperture, focus)
+    /// Parse a camera: an optional `named <NAME>`, then either a single pose
+    /// or one or more `key <FRAME> <POSE>` keyframes.
+    fn parse_camera(&mut self) -> Result<scene::Shot, scene::Error> {
+        let name = match self.peek()? {
+        | Some(scene::Token::Named) => {
+            self.next()?;
+            Some(self.parse_name()?)
+        }
+        | _ => None,
+        };
+
+        if !matches!(self.peek()?, Some(scene::Token::Key)) {
+            let track = camera::Track::fixed(self.parse_pose()?);
+            return Ok(scene::Shot { name, track })
+        }
+
+        let mut keys = Vec::new();
+        while let Some(scene::Token::Key) = self.peek()? {
+            self.next()?;
+            let frame = self.parse_float()?;
+            keys.push((frame, self.parse_pose()?));
+        }
+        Ok(scene::Shot { name, track: camera::Track::new(keys) })
     }
 
-    fn parse_integrator(&mut self) -> &'scene integrator::Any {
+    pub fn parse_shutter(&mut self) -> Result<camera::Shutter, scene::Error> {
+        let open = self.parse_float()?;
+        let close = self.parse_float()?;
+        Ok(camera::Shutter::new(open, close))
+    }
+
+    fn parse_pose(&mut self) -> Result<camera::Pose, scene::Error> {
+        Ok(camera::Pose {
+            origin: self.parse_vec()?,
+            toward: self.parse_vec()?,
+            up: self.parse_vec()?,
+            fov: self.parse_float()?,
+            aspect: self.parse_float()?,
+            aperture: self.parse_float()?,
+            focus: self.parse_float()?,
+        })
+    }
+
+    pub fn parse_integrator(&mut self) -> Result<&'scene integrator::Any, scene::Error> {
         use scene::Token::*;
-        match self.lexer.next() {
-        | Some(Normal) => self.arena.alloc(integrator::Any::Normal(integrator::Normal)),
-        | Some(Path) => {
-            let depth = self.parse_int() as usize;
-            let threshold = self.parse_float();
-            self.arena.alloc(integrator::Any::Path(
+        match self.next()? {
+        | (_, Some(Normal)) => Ok(self.arena.alloc(integrator::Any::Normal(integrator::Normal))),
+        | (_, Some(Path)) => {
+            let depth = self.parse_int()? as usize;
+            let threshold = self.parse_float()?;
+            Ok(self.arena.alloc(integrator::Any::Path(
                 integrator::Path::new(depth, threshold)
-            ))
+            )))
+        }
+        | (_, Some(BDPT)) => {
+            let depth = self.parse_int()? as usize;
+            Ok(self.arena.alloc(integrator::Any::BDPT(integrator::BDPT::new(depth))))
+        }
+        | (_, Some(Photon)) => {
+            let depth = self.parse_int()? as usize;
+            let count = self.parse_int_where("positive photon count", |count| count > 0)? as usize;
+            let radius = self.parse_float_where("positive gather radius", |radius| radius > 0.0)?;
+            Ok(self.arena.alloc(integrator::Any::Photon(
+                integrator::Photon::new(depth, count, radius)
+            )))
         }
-        | Some(Light) => self.arena.alloc(integrator::Any::Light(integrator::Light)),
-        | Some(BxDF) => self.arena.alloc(integrator::Any::BxDF(integrator::BxDF)),
-        | Some(Point) => self.arena.alloc(integrator::Any::Point(integrator::Point)),
-        | _ => panic!("[SCENE ERROR]: expected integrator"),
+        | (_, Some(SPPM)) => {
+            let depth = self.parse_int()? as usize;
+            let count = self.parse_int_where("positive photon count", |count| count > 0)? as usize;
+            let radius = self.parse_float_where("positive gather radius", |radius| radius > 0.0)?;
+            let alpha = self.parse_float_where("alpha between 0 and 1", |alpha| alpha > 0.0 && alpha < 1.0)?;
+            Ok(self.arena.alloc(integrator::Any::Photon(
+                integrator::Photon::progressive(depth, count, radius, alpha)
+            )))
+        }
+        | (_, Some(AO)) => {
+            let rays = self.parse_int()? as usize;
+            let distance = self.parse_float()?;
+            Ok(self.arena.alloc(integrator::Any::AO(integrator::AO::new(rays, distance))))
+        }
+        | (_, Some(Distance)) => Ok(self.arena.alloc(integrator::Any::Distance(integrator::Distance))),
+        | (_, Some(UV)) => Ok(self.arena.alloc(integrator::Any::UV(integrator::UV))),
+        | (_, Some(Albedo)) => Ok(self.arena.alloc(integrator::Any::Albedo(integrator::Albedo))),
+        | (_, Some(Material)) => Ok(self.arena.alloc(integrator::Any::Material(integrator::Material))),
+        | (_, Some(Object)) => Ok(self.arena.alloc(integrator::Any::Object(integrator::Object))),
+        | (_, Some(Heatmap)) => {
+            let work = match self.next()? {
+            | (_, Some(Nodes)) => integrator::Work::Nodes,
+            | (_, Some(Tests)) => integrator::Work::Tests,
+            | (position, found) => return Err(self.unexpected(position, "nodes or tests", found)),
+            };
+            let max = self.parse_float()?;
+            Ok(self.arena.alloc(integrator::Any::Heatmap(integrator::Heatmap::new(work, max))))
+        }
+        | (_, Some(Light)) => Ok(self.arena.alloc(integrator::Any::Light(integrator::Light))),
+        | (_, Some(BxDF)) => Ok(self.arena.alloc(integrator::Any::BxDF(integrator::BxDF))),
+        | (_, Some(Point)) => Ok(self.arena.alloc(integrator::Any::Point(integrator::Point))),
+        | (position, found) => Err(self.unexpected(position, "integrator", found)),
         }
     }
 
-    fn parse_light(&mut self) -> (&'scene light::Any<'scene>, Option<&'scene geom::Any<'scene>>) {
+    pub fn parse_tonemap(&mut self) -> Result<tone::Any, scene::Error> {
         use scene::Token::*;
-        match self.lexer.next() {
-        | Some(Point) => {
-            let p = self.parse_vec();
-            let i = self.parse_vec();
-            (self.arena.alloc(light::Any::Point(light::Point::new(p, i))), None)
-        }
-        | Some(Quad) => {
-            let p = self.parse_vec();
-            let u = self.parse_vec();
-            let v = self.parse_vec();
-            let bxdf = self.parse_bxdf();
-            let emit = Some(self.parse_vec());
+        match self.next()? {
+        | (_, Some(Clamp)) => Ok(tone::Any::Clamp(tone::Clamp)),
+        | (_, Some(Reinhard)) => Ok(tone::Any::Reinhard(tone::Reinhard)),
+        | (_, Some(Extended)) => {
+            let white = self.parse_float_where("positive white point", |white| white > 0.0)?;
+            Ok(tone::Any::ExtendedReinhard(tone::ExtendedReinhard::new(white)))
+        }
+        | (_, Some(ACES)) => Ok(tone::Any::ACES(tone::ACES)),
+        | (position, found) => Err(self.unexpected(position, "tone mapping operator", found)),
+        }
+    }
+
+    pub fn parse_transfer(&mut self) -> Result<tone::Transfer, scene::Error> {
+        use scene::Token::*;
+        match self.next()? {
+        | (_, Some(Gamma)) => Ok(tone::Transfer::Gamma(self.parse_float_where("positive exponent", |gamma| gamma > 0.0)?)),
+        | (_, Some(SRGB)) => Ok(tone::Transfer::SRGB),
+        | (position, found) => Err(self.unexpected(position, "transfer function", found)),
+        }
+    }
+
+    pub fn parse_filter(&mut self) -> Result<filter::Any, scene::Error> {
+        use scene::Token::*;
+        let positive = |x: f32| x > 0.0;
+        match self.next()? {
+        | (_, Some(Box)) => Ok(filter::Any::Box(filter::Box::new(self.parse_float_where("positive radius", positive)?))),
+        | (_, Some(Tent)) => Ok(filter::Any::Tent(filter::Tent::new(self.parse_float_where("positive radius", positive)?))),
+        | (_, Some(Gaussian)) => {
+            let radius = self.parse_float_where("positive radius", positive)?;
+            let alpha = self.parse_float_where("positive falloff", positive)?;
+            Ok(filter::Any::Gaussian(filter::Gaussian::new(radius, alpha)))
+        }
+        | (_, Some(Mitchell)) => {
+            let radius = self.parse_float_where("positive radius", positive)?;
+            let b = self.parse_float_where("B in [0, 1]", |b| (0.0..=1.0).contains(&b))?;
+            let c = self.parse_float_where("C in [0, 1]", |c| (0.0..=1.0).contains(&c))?;
+            Ok(filter::Any::Mitchell(filter::Mitchell::new(radius, b, c)))
+        }
+        | (_, Some(Lanczos)) => {
+            let radius = self.parse_float_where("positive radius", positive)?;
+            let tau = self.parse_float_where("positive lobe count", positive)?;
+            Ok(filter::Any::Lanczos(filter::Lanczos::new(radius, tau)))
+        }
+        | (position, found) => Err(self.unexpected(position, "reconstruction filter", found)),
+        }
+    }
+
+    pub fn parse_adaptive(&mut self) -> Result<adaptive::Adaptive, scene::Error> {
+        let min = self.parse_int()? as usize;
+        let threshold = self.parse_float()?;
+        Ok(adaptive::Adaptive::new(min, threshold))
+    }
+
+    pub fn parse_schedule(&mut self) -> Result<schedule::Schedule, scene::Error> {
+        use scene::Token::*;
+        let size = self.parse_int()? as usize;
+        let order = match self.next()? {
+        | (_, Some(Scanline)) => schedule::Order::Scanline,
+        | (_, Some(Spiral)) => schedule::Order::Spiral,
+        | (_, Some(Hilbert)) => schedule::Order::Hilbert,
+        | (position, found) => return Err(self.unexpected(position, "tile order", found)),
+        };
+        Ok(schedule::Schedule::new(size, order))
+    }
+
+    /// Crop window given as `x0 y0 x1 y1`, in pixels or, after `normalized`, as fractions.
+    pub fn parse_crop(&mut self) -> Result<film::Crop, scene::Error> {
+        if let Some(scene::Token::Normalized) = self.peek()? {
+            self.next()?;
+            let x0 = self.parse_float()?;
+            let y0 = self.parse_float()?;
+            let x1 = self.parse_float()?;
+            let y1 = self.parse_float()?;
+            return Ok(film::Crop::Normalized { x0, y0, x1, y1 })
+        }
+        let x0 = self.parse_int()? as usize;
+        let y0 = self.parse_int()? as usize;
+        let x1 = self.parse_int()? as usize;
+        let y1 = self.parse_int()? as usize;
+        Ok(film::Crop::Pixels(film::Bounds::new(x0, y0, x1, y1)))
+    }
+
+    pub fn parse_sampler(&mut self) -> Result<sample::Any, scene::Error> {
+        use scene::Token::*;
+        match self.next()? {
+        | (_, Some(Independent)) => Ok(sample::Any::Independent(sample::Independent::default())),
+        | (_, Some(Stratified)) => Ok(sample::Any::Stratified(sample::Stratified::default())),
+        | (_, Some(Sobol)) => Ok(sample::Any::Sobol(sample::Sobol::default())),
+        | (position, found) => Err(self.unexpected(position, "sampler", found)),
+        }
+    }
+
+    fn parse_light(&mut self) -> Result<(&'scene light::Any<'scene>, Option<&'scene geom::Any<'scene>>), scene::Error> {
+        use scene::Token::*;
+        match self.next()? {
+        | (_, Some(Point)) => {
+            let p = self.parse_vec()?;
+            let i = self.parse_vec()?;
+            Ok((self.arena.alloc(light::Any::Point(light::Point::new(p, i))), None))
+        }
+        | (_, Some(Quad)) => {
+            let p = self.parse_vec()?;
+            let u = self.parse_vec()?;
+            let v = self.parse_vec()?;
+            let bxdf = self.parse_bxdf()?;
+            let emit = Some(self.parse_vec()?);
             let light = self.arena.alloc(light::Any::Quad(geom::Quad::new(p, u, v, bxdf, emit)));
             let surface = self.arena.alloc(geom::Any::Quad(geom::Quad::new(p, u, v, bxdf, emit)));
-            (light, Some(surface))
+            Ok((light, Some(surface)))
         }
-        | _ => panic!("[SCENE ERROR]: expected light"),
+        | (position, found) => Err(self.unexpected(position, "light", found)),
         }
     }
 
-    fn parse_surface(&mut self) -> &'scene geom::Any<'scene> {
+    fn parse_surface(&mut self) -> Result<&'scene geom::Any<'scene>, scene::Error> {
         use scene::Token::*;
-        match self.lexer.next() {
-        | Some(Sphere) => {
-            let center = self.parse_vec();
-            let radius = self.parse_float();
-            let bxdf = self.parse_bxdf();
-            self.arena.alloc(geom::Any::Sphere(
+        match self.next()? {
+        | (_, Some(Sphere)) => {
+            let center = self.parse_vec()?;
+            let radius = self.parse_float()?;
+            let bxdf = self.parse_bxdf()?;
+            Ok(self.arena.alloc(geom::Any::Sphere(
                 geom::Sphere::new(center, radius, bxdf)
-            ))
+            )))
         }
-        | Some(Quad) => {
-            let p = self.parse_vec();
-            let u = self.parse_vec();
-            let v = self.parse_vec();
-            let bxdf = self.parse_bxdf();
+        | (_, Some(Quad)) => {
+            let p = self.parse_vec()?;
+            let u = self.parse_vec()?;
+            let v = self.parse_vec()?;
+            let bxdf = self.parse_bxdf()?;
             let emit = None;
-            self.arena.alloc(geom::Any::Quad(
+            Ok(self.arena.alloc(geom::Any::Quad(
                 geom::Quad::new(p, u, v, bxdf, emit)
-            ))
+            )))
+        }
+        | (_, Some(Mesh)) => self.parse_mesh(),
+        | (_, Some(Instance)) => {
+            let (position, token) = self.next()?;
+            let name = match token.as_ref().and_then(|token| self.reference(token)) {
+            | Some(name) => name,
+            | None => return Err(self.unexpected(position, "prototype name", token)),
+            };
+            let prototype = match self.prototypes.get(&name) {
+            | Some(prototype) => *prototype,
+            | None => return Err(self.error(position, scene::ErrorKind::Undefined(name))),
+            };
+            let bxdf = self.parse_bxdf()?;
+            Ok(self.arena.alloc(geom::Any::Mesh(
+                geom::Mesh::new(bxdf, prototype)
+            )))
+        }
+        | (_, Some(Moving)) => {
+            let mut keys = Vec::new();
+            while let Some(Key) = self.peek()? {
+                self.next()?;
+                let time = self.parse_float()?;
+                keys.push((time, self.parse_key()?));
+            }
+            if keys.is_empty() {
+                let (position, found) = self.next()?;
+                return Err(self.unexpected(position, "key", found))
+            }
+            let surface = self.parse_surface()?;
+            Ok(self.arena.alloc(geom::Any::Motion(
+                geom::Motion::new(self.arena, keys, surface)
+            )))
         }
-        | Some(Mesh) => self.parse_mesh(),
-        | _ => panic!("[SCENE ERROR]: expected surface"),
+        | (_, Some(Transform)) => {
+            let matrix = self.parse_transform()?;
+            let surface = self.parse_surface()?;
+            Ok(self.arena.alloc(geom::Any::Transform(
+                geom::Transform::new(matrix, surface)
+            )))
+        }
+        | (position, found) => Err(self.unexpected(position, "surface", found)),
         }
     }
 
-    fn parse_mesh(&mut self) -> &'scene geom::Any<'scene> {
+    /// Parses the placement at one motion key: either an offset, or a `{ ... }` block
+    /// of translations, rotations in degrees and scales, which are combined separately
+    /// and applied as scale, then rotation, then translation so that they interpolate.
+    fn parse_key(&mut self) -> Result<geom::Key, scene::Error> {
         use scene::Token::*;
-        let format = self.lexer.next();
-        let path = self.parse_string();
-        let bxdf = self.parse_bxdf();
-        let mesh = match format {
-        | Some(OBJ) => model::obj::parse(path, &self.arena, bxdf),
-        | Some(STL) => model::stl::parse(path, &self.arena, bxdf),
-        | _ => panic!("[SCENE ERROR]: expected mesh"),
+        let start = match self.peek()? {
+        | Some(LBrace) => self.next()?.0,
+        | _ => return Ok(geom::Key { translate: self.parse_vec()?, ..geom::Key::default() }),
         };
-        self.arena.alloc(geom::Any::Mesh(mesh))
+        let mut key = geom::Key::default();
+        loop {
+            match self.next()? {
+            | (_, Some(RBrace)) => break,
+            | (_, Some(Translate)) => key.translate += self.parse_vec()?,
+            | (_, Some(Rotate)) => {
+                let (axis, theta) = self.parse_rotation(start)?;
+                key.rotate = math::Quat::rotate(axis, theta) * key.rotate;
+            }
+            | (_, Some(Scale)) => key.scale *= self.parse_vec()?,
+            | (position, found) => return Err(self.unexpected(position, "translate, rotate, scale or }", found)),
+            }
+        }
+        if key.scale.x() * key.scale.y() * key.scale.z() == 0.0 {
+            return Err(self.error(start, scene::ErrorKind::Singular))
+        }
+        Ok(key)
     }
 
-    fn parse_bxdf(&mut self) -> &'scene bxdf::Any<'scene> {
+    /// Parses a `{ ... }` block of steps applied to the surface in the
+    /// order they are written, with rotation angles in degrees.
+    fn parse_transform(&mut self) -> Result<math::Mat4, scene::Error> {
         use scene::Token::*;
-        match self.lexer.next() {
-        | Some(Glazed) => {
-            let eta = self.parse_float();
-            let bxdf = self.parse_bxdf();
-            self.arena.alloc(bxdf::Any::Glazed(
+        let start = match self.next()? {
+        | (position, Some(LBrace)) => position,
+        | (position, found) => return Err(self.unexpected(position, "{", found)),
+        };
+        let mut matrix = math::Mat4::identity();
+        loop {
+            let step = match self.next()? {
+            | (_, Some(RBrace)) => break,
+            | (_, Some(Translate)) => math::Mat4::translate(self.parse_vec()?),
+            | (_, Some(Rotate)) => {
+                let (axis, theta) = self.parse_rotation(start)?;
+                math::Mat4::rotate(axis, theta)
+            }
+            | (_, Some(Scale)) => math::Mat4::scale(self.parse_vec()?),
+            | (position, found) => return Err(self.unexpected(position, "translate, rotate, scale or }", found)),
+            };
+            matrix = step * matrix;
+        }
+        match matrix.inverse() {
+        | Some(_) => Ok(matrix),
+        | None => Err(self.error(start, scene::ErrorKind::Singular)),
+        }
+    }
+
+    /// Parses an angle in degrees and an axis, returning the axis and angle in radians.
+    ///
+    /// A zero axis has no direction to rotate about, which makes the block at `start` singular.
+    fn parse_rotation(&mut self, start: scene::Position) -> Result<(math::Vec3, f32), scene::Error> {
+        let degrees = self.parse_float()?;
+        let axis = self.parse_vec()?;
+        if axis.len_sq() == 0.0 {
+            return Err(self.error(start, scene::ErrorKind::Singular))
+        }
+        Ok((axis, degrees * math::PI / 180.0))
+    }
+
+    fn parse_mesh(&mut self) -> Result<&'scene geom::Any<'scene>, scene::Error> {
+        let prototype = self.parse_model()?;
+        let bxdf = self.parse_bxdf()?;
+        Ok(self.arena.alloc(geom::Any::Mesh(
+            geom::Mesh::new(bxdf, prototype)
+        )))
+    }
+
+    /// Parse a mesh format and file, loading each file at most once per format
+    /// no matter how many meshes and prototypes refer to it.
+    fn parse_model(&mut self) -> Result<geom::Prototype<'scene>, scene::Error> {
+        use scene::Token::*;
+        let format = match self.next()? {
+        | (_, Some(OBJ)) => Format::OBJ,
+        | (_, Some(STL)) => Format::STL,
+        | (position, found) => return Err(self.unexpected(position, "mesh format", found)),
+        };
+        let (position, path) = match self.next()? {
+        | (position, Some(String(file))) => (position, self.resolve(&file)),
+        | (position, found) => return Err(self.unexpected(position, "string", found)),
+        };
+        let canonical = path
+            .canonicalize()
+            .unwrap_or_else(|_| path.clone());
+        let key = (format, canonical);
+        if let Some(prototype) = self.models.get(&key) {
+            return Ok(*prototype)
+        }
+        let prototype = match format {
+        | Format::OBJ => model::obj::parse(&path, self.arena),
+        | Format::STL => model::stl::parse(&path, self.arena),
+        };
+        let prototype = prototype.and_then(|prototype| {
+            // Mesh data lives outside the scene file, so hash the model itself
+            self.contents.write(&fs::read(&path)?);
+            Ok(prototype)
+        });
+        let prototype = match prototype {
+        | Ok(prototype) => prototype,
+        | Err(error) => {
+            let error = io::Error::new(
+                error.kind(),
+                format!("could not load '{}': {}", path.display(), error),
+            );
+            return Err(self.error(position, scene::ErrorKind::Io(error)))
+        }
+        };
+        self.models.insert(key, prototype);
+        Ok(prototype)
+    }
+
+    fn parse_bxdf(&mut self) -> Result<&'scene bxdf::Any<'scene>, scene::Error> {
+        use scene::Token::*;
+        match self.next()? {
+        | (_, Some(Glazed)) => {
+            let eta = self.parse_float()?;
+            let bxdf = self.parse_bxdf()?;
+            Ok(self.alloc_bxdf(bxdf::Any::Glazed(
                 bxdf::Glazed::new(bxdf, eta)
-            ))
+            )))
         }
-        | Some(Mirror) => {
-            self.arena.alloc(bxdf::Any::Mirror(bxdf::Mirror))
+        | (_, Some(Mirror)) => {
+            Ok(self.alloc_bxdf(bxdf::Any::Mirror(bxdf::Mirror)))
         }
-        | Some(Lambertian) => {
-            let color = self.parse_vec();
-            self.arena.alloc(bxdf::Any::Lambertian(
+        | (_, Some(Lambertian)) => {
+            let color = self.parse_vec()?;
+            Ok(self.alloc_bxdf(bxdf::Any::Lambertian(
                 bxdf::Lambertian::new(color)
-            ))
+            )))
         }
-        | Some(Specular) => {
-            let color = self.parse_vec();
-            let eta = self.parse_float();
-            self.arena.alloc(bxdf::Any::Specular(
+        | (_, Some(Specular)) => {
+            let color = self.parse_vec()?;
+            let eta = self.parse_float()?;
+            Ok(self.alloc_bxdf(bxdf::Any::Specular(
                 bxdf::Specular::new(color, eta)
-            ))
+            )))
         },
-        | _ => panic!("[SCENE ERROR]: expected BxDF"),
+        | (position, found) => match found.as_ref().and_then(|token| self.reference(token)) {
+            | Some(name) if self.materials.contains_key(&name) => Ok(self.materials[&name]),
+            | Some(name) => match self.constants.get(&name) {
+                | Some(Constant::BxDF(bxdf)) => Ok(*bxdf),
+                | Some(_) => Err(self.unexpected(position, "BxDF", found)),
+                | None => Err(self.error(position, scene::ErrorKind::Undefined(name))),
+            }
+            | None => Err(self.unexpected(position, "BxDF", found)),
+        }
         }
     }
 
-    fn parse_vec(&mut self) -> math::Vec3 {
-        let x = self.parse_float();
-        let y = self.parse_float();
-        let z = self.parse_float();
-        math::Vec3::new(x, y, z)
+    /// Allocate `bxdf`, giving it the next material ID.
+    fn alloc_bxdf(&mut self, bxdf: bxdf::Any<'scene>) -> &'scene bxdf::Any<'scene> {
+        let bxdf = self.arena.alloc(bxdf);
+        self.bxdfs.push(bxdf);
+        bxdf
     }
 
-    fn parse_string(&mut self) -> String {
-        match self.lexer.next() {
-        | Some(scene::Token::String(s)) => s,
-        | _ => panic!("[SCENE ERROR]: expected String"),
+    fn parse_vec(&mut self) -> Result<math::Vec3, scene::Error> {
+        if let Some(name) = self.peek()?.and_then(|token| self.reference(&token)) {
+            if let Some(Constant::Vector(v)) = self.constants.get(&name) {
+                let v = *v;
+                self.next()?;
+                return Ok(v)
+            }
         }
+        let x = self.parse_float()?;
+        let y = self.parse_float()?;
+        let z = self.parse_float()?;
+        Ok(math::Vec3::new(x, y, z))
     }
 
-    fn parse_int(&mut self) -> i32 {
-        match self.lexer.next() {
-        | Some(scene::Token::Int(i)) => i,
-        | _ => panic!("[SCENE ERROR]: expected integer"),
+    /// Parse a name being defined, which may be any string or keyword.
+    fn parse_name(&mut self) -> Result<String, scene::Error> {
+        match self.next()? {
+        | (_, Some(token)) if token.is_name() => Ok(token.to_string()),
+        | (position, found) => Err(self.unexpected(position, "name", found)),
         }
     }
 
-    fn parse_float(&mut self) -> f32 {
-        match self.lexer.next() {
-        | Some(scene::Token::Int(i)) => i as f32,
-        | Some(scene::Token::Float(f)) => f,
-        | _ => panic!("[SCENE ERROR]: expected float"),
+    /// Name that `token` refers to where a value is expected: any string, or
+    /// a keyword that has been given a meaning by `define`, `material` or `prototype`.
+    fn reference(&self, token: &scene::Token) -> Option<String> {
+        match token {
+        | scene::Token::String(name) => Some(name.clone()),
+        | token if token.is_name() => {
+            let name = token.to_string();
+            let defined = self.constants.contains_key(&name)
+                || self.materials.contains_key(&name)
+                || self.prototypes.contains_key(&name);
+            defined.then_some(name)
+        }
+        | _ => None,
+        }
+    }
+
+    fn parse_string(&mut self) -> Result<String, scene::Error> {
+        match self.next()? {
+        | (_, Some(scene::Token::String(s))) => Ok(s),
+        | (position, found) => Err(self.unexpected(position, "string", found)),
+        }
+    }
+
+    /// Fail unless every token has been consumed, such as after an override spec.
+    pub fn parse_end(&mut self) -> Result<(), scene::Error> {
+        match self.next()? {
+        | (_, None) => Ok(()),
+        | (position, found) => Err(self.unexpected(position, "end of input", found)),
+        }
+    }
+
+    fn parse_int(&mut self) -> Result<i32, scene::Error> {
+        match self.next()? {
+        | (_, Some(scene::Token::Int(i))) => Ok(i),
+        | (position, found) => Err(self.unexpected(position, "integer", found)),
+        }
+    }
+
+    /// Parse an integer, rejecting it as not being `expected` unless it is `valid`.
+    fn parse_int_where<F>(&mut self, expected: &'static str, valid: F) -> Result<i32, scene::Error>
+    where F: FnOnce(i32) -> bool {
+        match self.next()? {
+        | (_, Some(scene::Token::Int(i))) if valid(i) => Ok(i),
+        | (position, found) => Err(self.unexpected(position, expected, found)),
+        }
+    }
+
+    fn parse_float(&mut self) -> Result<f32, scene::Error> {
+        self.parse_float_where("float", |_| true)
+    }
+
+    /// Parse a float, rejecting it as not being `expected` unless it is `valid`.
+    fn parse_float_where<F>(&mut self, expected: &'static str, valid: F) -> Result<f32, scene::Error>
+    where F: FnOnce(f32) -> bool {
+        let (position, token) = self.next()?;
+        let f = match &token {
+        | Some(scene::Token::Int(i)) => *i as f32,
+        | Some(scene::Token::Float(f)) => *f,
+        | Some(other) => match self.reference(other) {
+            | Some(name) => match self.constants.get(&name) {
+                | Some(Constant::Float(f)) => *f,
+                | Some(_) => return Err(self.unexpected(position, expected, token)),
+                | None => return Err(self.error(position, scene::ErrorKind::Undefined(name))),
+            }
+            | None => return Err(self.unexpected(position, expected, token)),
         }
+        | None => return Err(self.unexpected(position, expected, token)),
+        };
+        if valid(f) { Ok(f) } else { Err(self.unexpected(position, expected, token)) }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Error message from parsing `source` as a scene.
+    fn error(source: &str) -> String {
+        let arena = arena::Arena::default();
+        Parser::new(&arena, scene::Lexer::new(source.as_bytes()))
+            .parse_scene()
+            .unwrap_err()
+            .to_string()
+    }
+
+    #[test]
+    fn keywords_can_be_names() {
+        let source = "define box 0.5\n\
+            material normal lambertian box box box\n\
+            camera named key 0 0 5 0 0 0 0 1 0 45 1 0 1\n\
+            surface sphere 0 0 0 box normal\n";
+        let arena = arena::Arena::default();
+        let scene = Parser::new(&arena, scene::Lexer::new(source.as_bytes()))
+            .parse_scene()
+            .unwrap();
+        assert_eq!(scene.shots()[0].name.as_deref(), Some("key"));
+    }
+
+    #[test]
+    fn rejects_undefined_keyword_value() {
+        let source = "camera 0 0 5 0 0 0 0 1 0 45 1 0 1\nsurface sphere 0 0 0 box lambertian 1 1 1\n";
+        assert_eq!(error(source), "2:22: expected float, found 'box'");
+    }
+
+    #[test]
+    fn rejects_unexpected_token() {
+        assert_eq!(error("width 10\n  frobnicate\n"), "2:3: expected top-level element, found 'frobnicate'");
+    }
+
+    #[test]
+    fn rejects_negative_width() {
+        assert_eq!(error("width -5\n"), "1:7: expected positive width, found '-5'");
+    }
+
+    #[test]
+    fn rejects_zero_height() {
+        assert_eq!(error("width 10\nheight 0\n"), "2:8: expected positive height, found '0'");
+    }
+
+    #[test]
+    fn rejects_missing_camera() {
+        assert_eq!(error("width 10\nheight 10\n"), "3:1: scene has no camera");
+    }
+
+    #[test]
+    fn rejects_zero_rotation_axis() {
+        let source = "camera 0 0 5 0 0 0 0 1 0 45 1 0 1\nsurface moving key 1 { rotate 90 0 0 0 } sphere 0 0 0 1 lambertian 1 1 1\n";
+        assert_eq!(error(source), "2:22: transform is not invertible");
+    }
+
+    #[test]
+    fn rejects_zero_samples() {
+        assert_eq!(error("samples  0"), "1:10: expected positive sample count, found '0'");
     }
 }
@@ -1,27 +1,93 @@
+use std::fmt;
+use std::hash;
+use std::mem;
+
 #[derive(Clone, Debug)]
 pub enum Token {
     String(String),
     Int(i32),
     Float(f32),
 
+    LBrace,
+    RBrace,
+
     Width,
     Height,
+    Aspect,
     Samples,
 
+    Define,
+    Include,
+    Material,
+    Prototype,
+
     Camera,
+    Named,
+    Key,
+    Frames,
+    Shutter,
     Integrator,
 
+    Tonemap,
+    Exposure,
+    Transfer,
+    Clamp,
+    Reinhard,
+    Extended,
+    ACES,
+    Gamma,
+    SRGB,
+
+    Filter,
+    Box,
+    Tent,
+    Gaussian,
+    Mitchell,
+    Lanczos,
+
+    Sampler,
+    Independent,
+    Stratified,
+    Sobol,
+    Seed,
+    Adaptive,
+
+    Tiles,
+    Scanline,
+    Spiral,
+    Hilbert,
+
+    Crop,
+    Normalized,
+
     Surface,
     Light,
     BxDF,
 
     Normal,
     Path,
- 
+    BDPT,
+    Photon,
+    SPPM,
+    AO,
+    Distance,
+    UV,
+    Albedo,
+    Object,
+    Heatmap,
+    Nodes,
+    Tests,
+
     Sphere,
     Quad,
     Point,
     Mesh,
+    Instance,
+    Moving,
+    Transform,
+    Translate,
+    Rotate,
+    Scale,
     OBJ,
     STL,
 
@@ -30,3 +96,125 @@ pub enum Token {
     Lambertian,
     Specular,
 }
+
+/// Location of a token in its source file.
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
+pub struct Position {
+    /// One-indexed line number
+    pub line: usize,
+
+    /// One-indexed column number
+    pub col: usize,
+}
+
+impl Token {
+    /// Whether this token can be used as a name: any string or keyword, but not a number or brace.
+    pub fn is_name(&self) -> bool {
+        !matches!(self, Token::Int(_) | Token::Float(_) | Token::LBrace | Token::RBrace)
+    }
+}
+
+impl hash::Hash for Token {
+    fn hash<H: hash::Hasher>(&self, state: &mut H) {
+        mem::discriminant(self).hash(state);
+        match self {
+        | Token::String(string) => string.hash(state),
+        | Token::Int(int) => int.hash(state),
+        | Token::Float(float) => float.to_bits().hash(state),
+        | _ => (),
+        }
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use Token::*;
+        let keyword = match self {
+        | String(string) => return write!(fmt, "{}", string),
+        | Int(int) => return write!(fmt, "{}", int),
+        | Float(float) => return write!(fmt, "{}", float),
+        | LBrace => "{",
+        | RBrace => "}",
+        | Width => "width",
+        | Height => "height",
+        | Aspect => "aspect",
+        | Samples => "samples",
+        | Define => "define",
+        | Include => "include",
+        | Material => "material",
+        | Prototype => "prototype",
+        | Camera => "camera",
+        | Named => "named",
+        | Key => "key",
+        | Frames => "frames",
+        | Shutter => "shutter",
+        | Integrator => "integrator",
+        | Tonemap => "tonemap",
+        | Exposure => "exposure",
+        | Transfer => "transfer",
+        | Clamp => "clamp",
+        | Reinhard => "reinhard",
+        | Extended => "extended",
+        | ACES => "aces",
+        | Gamma => "gamma",
+        | SRGB => "srgb",
+        | Filter => "filter",
+        | Box => "box",
+        | Tent => "tent",
+        | Gaussian => "gaussian",
+        | Mitchell => "mitchell",
+        | Lanczos => "lanczos",
+        | Sampler => "sampler",
+        | Independent => "independent",
+        | Stratified => "stratified",
+        | Sobol => "sobol",
+        | Seed => "seed",
+        | Adaptive => "adaptive",
+        | Tiles => "tiles",
+        | Scanline => "scanline",
+        | Spiral => "spiral",
+        | Hilbert => "hilbert",
+        | Crop => "crop",
+        | Normalized => "normalized",
+        | Surface => "surface",
+        | Light => "light",
+        | BxDF => "bxdf",
+        | Normal => "normal",
+        | Path => "path",
+        | BDPT => "bdpt",
+        | Photon => "photon",
+        | SPPM => "sppm",
+        | AO => "ao",
+        | Distance => "distance",
+        | UV => "uv",
+        | Albedo => "albedo",
+        | Object => "object",
+        | Heatmap => "heatmap",
+        | Nodes => "nodes",
+        | Tests => "tests",
+        | Sphere => "sphere",
+        | Quad => "quad",
+        | Point => "point",
+        | Mesh => "mesh",
+        | Instance => "instance",
+        | Moving => "moving",
+        | Transform => "transform",
+        | Translate => "translate",
+        | Rotate => "rotate",
+        | Scale => "scale",
+        | OBJ => "obj",
+        | STL => "stl",
+        | Glazed => "glazed",
+        | Mirror => "mirror",
+        | Lambertian => "lambertian",
+        | Specular => "specular",
+        };
+        write!(fmt, "{}", keyword)
+    }
+}
+
+impl fmt::Display for Position {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        write!(fmt, "{}:{}", self.line, self.col)
+    }
+}
@@ -0,0 +1,125 @@
+use crate::film;
+
+/// Order in which tiles are handed to render threads.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Order {
+    /// Left to right, top to bottom
+    Scanline,
+
+    /// Outward from the center of the image
+    Spiral,
+
+    /// Along a Hilbert curve, so consecutive tiles are always adjacent
+    Hilbert,
+}
+
+/// Rectangular block of pixels rendered as one unit of work.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Region {
+    /// Leftmost pixel column in the image
+    pub x: usize,
+
+    /// Topmost pixel row in the image
+    pub y: usize,
+
+    pub width: usize,
+    pub height: usize,
+
+    /// Horizontal position in the grid of tiles
+    pub column: usize,
+
+    /// Vertical position in the grid of tiles
+    pub row: usize,
+}
+
+/// Splits an image into square tiles and orders them for rendering.
+#[derive(Copy, Clone, Debug)]
+pub struct Schedule {
+    size: usize,
+    order: Order,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        Schedule::new(32, Order::Hilbert)
+    }
+}
+
+impl Schedule {
+    pub fn new(size: usize, order: Order) -> Self {
+        Schedule { size: size.max(1), order }
+    }
+
+    /// Number of tile columns and rows covering `bounds`.
+    pub fn grid(&self, bounds: &film::Bounds) -> (usize, usize) {
+        (bounds.width().div_ceil(self.size), bounds.height().div_ceil(self.size))
+    }
+
+    /// Tiles covering `bounds`, in rendering order.
+    pub fn split(&self, bounds: &film::Bounds) -> Vec<Region> {
+        let (columns, rows) = self.grid(bounds);
+        let mut cells = match self.order {
+        | Order::Scanline | Order::Spiral => (0..rows)
+            .flat_map(|row| (0..columns).map(move |column| (column, row)))
+            .collect::<Vec<_>>(),
+        | Order::Hilbert => {
+            let side = columns.max(rows).next_power_of_two();
+            (0..side * side)
+                .map(|index| hilbert(side, index))
+                .filter(|&(column, row)| column < columns && row < rows)
+                .collect()
+        }
+        };
+
+        if self.order == Order::Spiral {
+            let cx = (columns as f32 - 1.0) / 2.0;
+            let cy = (rows as f32 - 1.0) / 2.0;
+            let key = |&(column, row): &(usize, usize)| {
+                let dx = column as f32 - cx;
+                let dy = row as f32 - cy;
+                (dx.abs().max(dy.abs()), dy.atan2(dx))
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).expect("[INTERNAL ERROR]: NaN tile key"));
+        }
+
+        cells.into_iter()
+            .map(|(column, row)| {
+                let x = bounds.x0 + column * self.size;
+                let y = bounds.y0 + row * self.size;
+                Region {
+                    x,
+                    y,
+                    width: self.size.min(bounds.x1 - x),
+                    height: self.size.min(bounds.y1 - y),
+                    column,
+                    row,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Convert distance `index` along a Hilbert curve filling a `side x side` grid into coordinates.
+///
+/// See: https://en.wikipedia.org/wiki/Hilbert_curve#Applications_and_mapping_algorithms
+fn hilbert(side: usize, index: usize) -> (usize, usize) {
+    let (mut x, mut y) = (0, 0);
+    let mut t = index;
+    let mut s = 1;
+    while s < side {
+        let rx = 1 & (t / 2);
+        let ry = 1 & (t ^ rx);
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - x;
+                y = s - 1 - y;
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        x += s * rx;
+        y += s * ry;
+        t /= 4;
+        s *= 2;
+    }
+    (x, y)
+}
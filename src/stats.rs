@@ -1,10 +1,29 @@
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::cell::Cell;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread::LocalKey;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn on statistic tracking at runtime, regardless of the `stats` feature.
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether intersection and traversal statistics should be recorded.
+#[inline(always)]
+pub fn enabled() -> bool {
+    cfg!(feature = "stats") || ENABLED.load(Ordering::Relaxed)
+}
 
 macro_rules! counter {
     ($name:ident) => {
         pub static $name: Counter = Counter {
             name: stringify!($name),
             value: AtomicUsize::new(0),
+            local: {
+                thread_local!(static LOCAL: Cell<usize> = const { Cell::new(0) });
+                &LOCAL
+            },
         };
     }
 }
@@ -25,23 +44,51 @@ counter!(BVH_HITS);
 counter!(BVH_MISSES);
 counter!(SPHERE_INTERSECTION_TESTS);
 counter!(TRI_INTERSECTION_TESTS);
+counter!(QUAD_INTERSECTION_TESTS);
 counter!(LIST_INTERSECTION_TESTS);
 memory!(ARENA_MEMORY);
 
+/// Print all tracked statistics to stdout.
+pub fn print() {
+    println!("{}", ARENA_MEMORY);
+    println!("{}", INTERSECTION_TESTS);
+    println!("{}", BOUNDING_BOX_INTERSECTION_TESTS);
+    println!("{}", BVH_HITS);
+    println!("{}", BVH_MISSES);
+    println!("{}", SPHERE_INTERSECTION_TESTS);
+    println!("{}", TRI_INTERSECTION_TESTS);
+    println!("{}", QUAD_INTERSECTION_TESTS);
+    println!("{}", LIST_INTERSECTION_TESTS);
+}
+
 #[derive(Debug)]
 pub struct Counter {
     name: &'static str,
     value: AtomicUsize,
+
+    /// Share of `value` counted on the current thread
+    local: &'static LocalKey<Cell<usize>>,
 }
 
 impl Counter {
     pub fn inc(&self) {
-        self.value.fetch_add(1, Ordering::Relaxed);
+        self.add(1);
+    }
+
+    pub fn add(&self, value: usize) {
+        self.value.fetch_add(value, Ordering::Relaxed);
+        self.local.with(|local| local.set(local.get() + value));
     }
 
     pub fn read(&self) -> usize {
         self.value.load(Ordering::Acquire)
     }
+
+    /// Count so far on the current thread alone, so that the
+    /// difference across a call is the work that call did.
+    pub fn read_local(&self) -> usize {
+        self.local.with(Cell::get)
+    }
 }
 
 impl std::fmt::Display for Counter {
@@ -0,0 +1,166 @@
+use crate::math;
+use crate::math::Vec3;
+
+/// Maps linear radiance into the displayable `[0, 1]` range.
+pub trait ToneMap: std::fmt::Debug + Send + Sync {
+    fn map(&self, c: &Vec3) -> Vec3;
+}
+
+/// Discard everything outside `[0, 1]`.
+#[derive(Copy, Clone, Debug)]
+pub struct Clamp;
+
+impl ToneMap for Clamp {
+    fn map(&self, c: &Vec3) -> Vec3 {
+        c.max(&Vec3::broadcast(0.0)).min(&Vec3::broadcast(1.0))
+    }
+}
+
+/// Reinhard operator applied to luminance, which preserves hue.
+///
+/// See: http://www.cmap.polytechnique.fr/~peyre/cours/x2005signal/hdr_photographic.pdf
+#[derive(Copy, Clone, Debug)]
+pub struct Reinhard;
+
+impl ToneMap for Reinhard {
+    fn map(&self, c: &Vec3) -> Vec3 {
+        let l = luminance(c);
+        if l <= 0.0 { return Vec3::default() }
+        Clamp.map(&(c / (1.0 + l)))
+    }
+}
+
+/// Reinhard operator that maps luminance `white` to `1.0` instead of infinity.
+#[derive(Copy, Clone, Debug)]
+pub struct ExtendedReinhard {
+    white: f32,
+}
+
+impl ExtendedReinhard {
+    pub fn new(white: f32) -> Self {
+        ExtendedReinhard { white }
+    }
+}
+
+impl ToneMap for ExtendedReinhard {
+    fn map(&self, c: &Vec3) -> Vec3 {
+        let l = luminance(c);
+        if l <= 0.0 { return Vec3::default() }
+        let mapped = l * (1.0 + l / (self.white * self.white)) / (1.0 + l);
+        Clamp.map(&(c * (mapped / l)))
+    }
+}
+
+/// Krzysztof Narkowicz's fit of the ACES filmic curve.
+///
+/// See: https://knarkowicz.wordpress.com/2016/01/06/aces-filmic-tone-mapping-curve/
+#[derive(Copy, Clone, Debug)]
+pub struct ACES;
+
+impl ToneMap for ACES {
+    fn map(&self, x: &Vec3) -> Vec3 {
+        let (a, b, c, d, e) = (2.51, 0.03, 2.43, 0.59, 0.14);
+        let x = x.max(&Vec3::default());
+        let numerator = x * (x * a + Vec3::broadcast(b));
+        let denominator = x * (x * c + Vec3::broadcast(d)) + Vec3::broadcast(e);
+        Clamp.map(&(numerator / denominator))
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum Any {
+    Clamp(Clamp),
+    Reinhard(Reinhard),
+    ExtendedReinhard(ExtendedReinhard),
+    ACES(ACES),
+}
+
+impl ToneMap for Any {
+    fn map(&self, c: &Vec3) -> Vec3 {
+        match self {
+            Any::Clamp(tone) => tone.map(c),
+            Any::Reinhard(tone) => tone.map(c),
+            Any::ExtendedReinhard(tone) => tone.map(c),
+            Any::ACES(tone) => tone.map(c),
+        }
+    }
+}
+
+/// Opto-electronic transfer function used to encode display values.
+#[derive(Copy, Clone, Debug)]
+pub enum Transfer {
+    /// Pure power curve with the given exponent
+    Gamma(f32),
+
+    /// Piecewise sRGB curve
+    SRGB,
+}
+
+impl Transfer {
+    pub fn encode(&self, c: &Vec3) -> Vec3 {
+        match self {
+        | Transfer::Gamma(gamma) => Vec3::new(
+            c.x().powf(1.0 / gamma),
+            c.y().powf(1.0 / gamma),
+            c.z().powf(1.0 / gamma),
+        ),
+        | Transfer::SRGB => Vec3::new(srgb(c.x()), srgb(c.y()), srgb(c.z())),
+        }
+    }
+}
+
+/// Full display transform: exposure, tone mapping, then encoding.
+#[derive(Copy, Clone, Debug)]
+pub struct Tone {
+    /// Exposure adjustment in stops
+    pub exposure: f32,
+
+    /// Tone mapping operator
+    pub operator: Any,
+
+    /// Output encoding
+    pub transfer: Transfer,
+}
+
+impl Default for Tone {
+    /// Clamp and square root, the encoding images had before tone mapping was configurable.
+    fn default() -> Self {
+        Tone {
+            exposure: 0.0,
+            operator: Any::Clamp(Clamp),
+            transfer: Transfer::Gamma(2.0),
+        }
+    }
+}
+
+impl Tone {
+    /// Pass values in `[0, 1]` through unchanged, for data that is already display-encoded.
+    pub fn identity() -> Self {
+        Tone {
+            exposure: 0.0,
+            operator: Any::Clamp(Clamp),
+            transfer: Transfer::Gamma(1.0),
+        }
+    }
+
+    /// Convert linear radiance into an encoded display value in `[0, 1]`.
+    pub fn apply(&self, c: &Vec3) -> Vec3 {
+        let exposed = c * 2f32.powf(self.exposure);
+        let mapped = self.operator.map(&exposed);
+        self.transfer.encode(&mapped)
+    }
+}
+
+/// Relative luminance of linear sRGB primaries.
+pub fn luminance(c: &Vec3) -> f32 {
+    0.2126 * c.r() + 0.7152 * c.g() + 0.0722 * c.b()
+}
+
+/// See: https://en.wikipedia.org/wiki/SRGB#Transfer_function_(%22gamma%22)
+fn srgb(c: f32) -> f32 {
+    if c <= 0.003_130_8 {
+        12.92 * c
+    } else {
+        1.055 * math::max(c, 0.0).powf(1.0 / 2.4) - 0.055
+    }
+}
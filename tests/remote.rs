@@ -0,0 +1,94 @@
+use std::fs;
+use std::net;
+use std::path;
+use std::thread;
+
+use photon::arena;
+use photon::film;
+use photon::remote;
+use photon::scene;
+
+/// Start a worker on an ephemeral localhost port that serves a single coordinator.
+///
+/// Each worker gets a thread pool of its own, as it would in its own process.
+fn spawn_worker() -> String {
+    let listener = net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap().to_string();
+    let pool = rayon::ThreadPoolBuilder::new().num_threads(2).build().unwrap();
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        pool.install(|| remote::work(stream)).unwrap();
+    });
+    address
+}
+
+/// Cornell box scene file, with `overrides` appended as the command line would.
+fn source(overrides: &str) -> (path::PathBuf, Vec<u8>) {
+    let path = path::Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("scenes/cornell-box.txt")
+        .canonicalize()
+        .unwrap();
+    let mut source = fs::read(&path).unwrap();
+    source.extend_from_slice(overrides.as_bytes());
+    (path, source)
+}
+
+fn load<'scene>(arena: &'scene arena::Arena, path: &path::Path, source: &'scene [u8]) -> scene::Scene<'scene> {
+    scene::Parser::new(arena, scene::Lexer::new(source))
+        .with_path(path)
+        .parse_scene()
+        .unwrap()
+}
+
+fn assert_same(local: &film::Film, remote: &film::Film) {
+    assert_eq!((local.width(), local.height()), (remote.width(), remote.height()));
+    for (local, remote) in local.pixels().iter().zip(remote.pixels()) {
+        let (l, r) = (local.sum(), remote.sum());
+        assert_eq!((l.x(), l.y(), l.z()), (r.x(), r.y(), r.z()));
+        let (l, r) = (local.splat(), remote.splat());
+        assert_eq!((l.x(), l.y(), l.z()), (r.x(), r.y(), r.z()));
+        assert_eq!(local.weight().to_bits(), remote.weight().to_bits());
+        assert_eq!(local.samples(), remote.samples());
+    }
+}
+
+/// Render `overrides` of the Cornell box both locally and on two localhost workers.
+fn compare(overrides: &str) {
+    let (path, source) = source(overrides);
+    let arena = arena::Arena::default();
+    let scene = load(&arena, &path, &source);
+    let passes = scene.passes(&scene::Cursor::default(), true);
+
+    let mut local = film::Film::new(scene.width(), scene.height());
+    scene.render_passes(&mut local, &passes, None, |_, _| ());
+
+    let workers = [spawn_worker(), spawn_worker()];
+    let mut coordinator = remote::Coordinator::connect(&workers, &path, &source, &scene).unwrap();
+    let mut remote = film::Film::new(scene.width(), scene.height());
+    let render = |film: &mut film::Film, cursor: &scene::Cursor, merged: &mut (dyn FnMut(&film::Film, &scene::Cursor) + Send)| {
+        coordinator.render_pass(&scene, film, cursor, merged)
+    };
+    scene.render_passes_with(&mut remote, &passes, None, render, |_, _| ()).unwrap();
+
+    assert_same(&local, &remote);
+}
+
+#[test]
+fn path_matches_local() {
+    compare("\nwidth 24\nheight 24\nsamples 4\ntiles 8 hilbert\nintegrator path 4 1.0\n");
+}
+
+#[test]
+fn bdpt_matches_local() {
+    compare("\nwidth 16\nheight 16\nsamples 2\ntiles 8 scanline\nintegrator bdpt 3\n");
+}
+
+#[test]
+fn sppm_matches_local() {
+    compare("\nwidth 16\nheight 16\nsamples 3\ntiles 8 spiral\nintegrator sppm 3 2000 0.1 0.7\n");
+}
+
+#[test]
+fn adaptive_matches_local() {
+    compare("\nwidth 16\nheight 16\nsamples 8\ntiles 8 scanline\nadaptive 2 0.5\nintegrator path 3 1.0\n");
+}